@@ -0,0 +1,236 @@
+//! Adaptive integration time implementation
+use crate::{
+    measurement::{full_scale, lsb, EXPONENT_MAX},
+    mode, AdaptiveIntegration, AdaptiveSettings, Error, IntegrationTime, LuxRange, Measurement,
+    Opt300x,
+};
+use embedded_hal::i2c;
+
+/// Weight of the newest relative change in the activity
+const ACTIVITY_WEIGHT: f32 = 0.25;
+/// Ratio between the full-scale value of the selected manual range and the
+/// current illuminance
+const RANGE_HEADROOM: f32 = 2.0;
+
+impl AdaptiveIntegration {
+    /// Create a new instance.
+    pub fn new(settings: AdaptiveSettings) -> Self {
+        AdaptiveIntegration {
+            settings,
+            activity: 0.0,
+            previous: None,
+            conversions: 0,
+        }
+    }
+
+    /// Current activity.
+    ///
+    /// This is the smoothed relative change between consecutive conversions.
+    pub fn activity(&self) -> f32 {
+        self.activity
+    }
+
+    /// Start the controller.
+    ///
+    /// This sets the 100 ms integration time and, if the lux range is
+    /// adjusted, the automatic full-scale range selection.
+    ///
+    /// Returns `Error::InvalidInputData` if the slow activity is not lower
+    /// than the fast activity or if the dim illuminance is not lower than
+    /// the bright illuminance.
+    ///
+    /// Note that the conversion ready flag is cleared automatically
+    /// after calling this method.
+    pub fn start<I2C, IC>(
+        &mut self,
+        sensor: &mut Opt300x<I2C, IC, mode::Continuous>,
+    ) -> Result<(), Error<I2C::Error>>
+    where
+        I2C: i2c::I2c,
+    {
+        let settings = &self.settings;
+        let are_valid = settings.slow_activity >= 0.0
+            && settings.slow_activity < settings.fast_activity
+            && settings.dim_lux >= 0.0
+            && settings.dim_lux < settings.bright_lux;
+        if !are_valid {
+            return Err(Error::InvalidInputData);
+        }
+        self.activity = 0.0;
+        self.previous = None;
+        self.conversions = 0;
+        self.apply(sensor, IntegrationTime::Ms100, 0.0)
+    }
+
+    /// Read the next valid conversion and adapt the integration time.
+    ///
+    /// This returns `nb::Error::WouldBlock` until a conversion performed
+    /// entirely with the current configuration is ready. Conversions in
+    /// progress while the configuration was changed are discarded.
+    /// See [`wait_for_valid_conversion()`](Opt300x::wait_for_valid_conversion).
+    ///
+    /// 100 ms conversions are used when the activity or the illuminance is
+    /// high or if an overflow occurs. 800 ms conversions are used when the
+    /// activity and the illuminance are low. The integration time is kept for
+    /// at least the configured minimum number of conversions, unless an
+    /// overflow occurs.
+    pub fn poll<I2C, IC>(
+        &mut self,
+        sensor: &mut Opt300x<I2C, IC, mode::Continuous>,
+    ) -> nb::Result<Measurement<f32>, Error<I2C::Error>>
+    where
+        I2C: i2c::I2c,
+    {
+        let measurement = sensor.wait_for_valid_conversion()?;
+        let lux = measurement.result;
+        let has_overflown = measurement.status.has_overflown;
+        self.update(lux);
+        let current = measurement.info.integration_time;
+        let next = self.next_integration_time(current, lux, has_overflown);
+        if next != current {
+            self.apply(sensor, next, lux).map_err(nb::Error::Other)?;
+        }
+        Ok(measurement)
+    }
+
+    fn update(&mut self, lux: f32) {
+        if let Some(previous) = self.previous {
+            let change = (lux - previous).abs() / previous.max(lux).max(lsb(0));
+            self.activity = ACTIVITY_WEIGHT * change + (1.0 - ACTIVITY_WEIGHT) * self.activity;
+        }
+        self.previous = Some(lux);
+        self.conversions = self.conversions.saturating_add(1);
+    }
+
+    fn next_integration_time(
+        &self,
+        current: IntegrationTime,
+        lux: f32,
+        has_overflown: bool,
+    ) -> IntegrationTime {
+        let settings = &self.settings;
+        if has_overflown {
+            // The result is useless until the range is extended.
+            return IntegrationTime::Ms100;
+        }
+        if self.conversions < settings.min_conversions {
+            return current;
+        }
+        match current {
+            IntegrationTime::Ms800
+                if self.activity > settings.fast_activity || lux > settings.bright_lux =>
+            {
+                IntegrationTime::Ms100
+            }
+            IntegrationTime::Ms100
+                if self.activity < settings.slow_activity && lux < settings.dim_lux =>
+            {
+                IntegrationTime::Ms800
+            }
+            _ => current,
+        }
+    }
+
+    fn apply<I2C, IC>(
+        &mut self,
+        sensor: &mut Opt300x<I2C, IC, mode::Continuous>,
+        integration_time: IntegrationTime,
+        lux: f32,
+    ) -> Result<(), Error<I2C::Error>>
+    where
+        I2C: i2c::I2c,
+    {
+        if self.settings.adjust_range {
+            let range = match integration_time {
+                IntegrationTime::Ms100 => LuxRange::Auto,
+                IntegrationTime::Ms800 => manual_range(lux),
+            };
+            sensor.set_lux_range(range)?;
+        }
+        sensor.set_integration_time(integration_time)?;
+        self.conversions = 0;
+        Ok(())
+    }
+}
+
+/// Lowest manual range with some headroom over the illuminance
+fn manual_range(lux: f32) -> LuxRange {
+    let exponent = (0..=EXPONENT_MAX)
+        .find(|exponent| full_scale(*exponent) >= lux * RANGE_HEADROOM)
+        .unwrap_or(EXPONENT_MAX);
+    LuxRange::Manual(exponent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SETTINGS: AdaptiveSettings = AdaptiveSettings {
+        fast_activity: 0.2,
+        slow_activity: 0.05,
+        dim_lux: 100.0,
+        bright_lux: 200.0,
+        min_conversions: 2,
+        adjust_range: true,
+    };
+
+    fn controller(activity: f32, conversions: u32) -> AdaptiveIntegration {
+        AdaptiveIntegration {
+            activity,
+            conversions,
+            ..AdaptiveIntegration::new(SETTINGS)
+        }
+    }
+
+    #[test]
+    fn uses_long_conversions_when_stable_and_dim() {
+        let controller = controller(0.01, 2);
+        let next = controller.next_integration_time(IntegrationTime::Ms100, 50.0, false);
+        assert_eq!(IntegrationTime::Ms800, next);
+        let next = controller.next_integration_time(IntegrationTime::Ms100, 150.0, false);
+        assert_eq!(IntegrationTime::Ms100, next);
+    }
+
+    #[test]
+    fn uses_short_conversions_when_changing_or_bright() {
+        let next = controller(0.3, 2).next_integration_time(IntegrationTime::Ms800, 50.0, false);
+        assert_eq!(IntegrationTime::Ms100, next);
+        let next = controller(0.1, 2).next_integration_time(IntegrationTime::Ms800, 150.0, false);
+        assert_eq!(IntegrationTime::Ms800, next);
+        let next = controller(0.0, 2).next_integration_time(IntegrationTime::Ms800, 250.0, false);
+        assert_eq!(IntegrationTime::Ms100, next);
+        let next = controller(0.0, 2).next_integration_time(IntegrationTime::Ms800, 40.0, true);
+        assert_eq!(IntegrationTime::Ms100, next);
+    }
+
+    #[test]
+    fn keeps_integration_time_for_min_conversions() {
+        let next = controller(0.3, 1).next_integration_time(IntegrationTime::Ms800, 50.0, false);
+        assert_eq!(IntegrationTime::Ms800, next);
+    }
+
+    #[test]
+    fn switches_to_short_conversions_on_overflow_immediately() {
+        let controller = controller(0.0, 0);
+        let next = controller.next_integration_time(IntegrationTime::Ms800, 40.0, true);
+        assert_eq!(IntegrationTime::Ms100, next);
+        let next = controller.next_integration_time(IntegrationTime::Ms100, 40.0, true);
+        assert_eq!(IntegrationTime::Ms100, next);
+    }
+
+    #[test]
+    fn activity_follows_relative_changes() {
+        let mut controller = controller(0.0, 0);
+        controller.update(100.0);
+        assert_eq!(0.0, controller.activity());
+        controller.update(50.0);
+        assert!((controller.activity() - 0.125).abs() < 1e-6);
+    }
+
+    #[test]
+    fn selects_range_with_headroom() {
+        assert_eq!(LuxRange::LUX_40_95, manual_range(0.0));
+        assert_eq!(LuxRange::LUX_163_80, manual_range(50.0));
+        assert_eq!(LuxRange::LUX_83865_60, manual_range(50_000.0));
+    }
+}
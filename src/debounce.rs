@@ -0,0 +1,149 @@
+//! Software debouncing of the threshold flags
+use crate::{AlarmState, DebounceDelays, DebounceState, Debouncer, Persistence, Status};
+
+impl Debouncer {
+    /// Create a new instance with the delays for the high and low limit
+    /// alarms. Both alarms start deasserted.
+    pub fn new(high: DebounceDelays, low: DebounceDelays) -> Self {
+        Debouncer {
+            high: DebounceState::new(high),
+            low: DebounceState::new(low),
+        }
+    }
+
+    /// Current state of the alarms.
+    pub fn state(&self) -> AlarmState {
+        AlarmState {
+            high: self.high.asserted,
+            low: self.low.asserted,
+        }
+    }
+
+    /// Update the alarms with the status of a conversion.
+    ///
+    /// This should be called once per conversion with the status read from
+    /// the device and the current time in microseconds. The threshold flags
+    /// are set by the device after the number of faults configured with
+    /// [`set_fault_count()`](crate::Opt300x::set_fault_count), so the delays
+    /// here extend the hardware fault count.
+    ///
+    /// A flag must persist for the assert delay before its alarm is asserted
+    /// and remain cleared for the deassert delay before the alarm is
+    /// deasserted again.
+    ///
+    /// Returns the new state if any alarm changed.
+    pub fn update(&mut self, status: &Status, now_us: u64) -> Option<AlarmState> {
+        let high = self.high.update(status.was_too_high, now_us);
+        let low = self.low.update(status.was_too_low, now_us);
+        if high || low {
+            Some(self.state())
+        } else {
+            None
+        }
+    }
+}
+
+impl DebounceState {
+    fn new(delays: DebounceDelays) -> Self {
+        DebounceState {
+            delays,
+            asserted: false,
+            pending: None,
+        }
+    }
+
+    /// Returns whether the state changed
+    fn update(&mut self, condition: bool, now_us: u64) -> bool {
+        if condition == self.asserted {
+            self.pending = None;
+            return false;
+        }
+        let (since, count) = match self.pending {
+            Some((since, count)) => (since, count.saturating_add(1)),
+            None => (now_us, 1),
+        };
+        let delay = if condition {
+            self.delays.assert
+        } else {
+            self.delays.deassert
+        };
+        let has_persisted = match delay {
+            Persistence::Conversions(conversions) => count >= conversions,
+            Persistence::Microseconds(us) => now_us.saturating_sub(since) >= us,
+        };
+        if has_persisted {
+            self.asserted = condition;
+            self.pending = None;
+        } else {
+            self.pending = Some((since, count));
+        }
+        has_persisted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(was_too_high: bool, was_too_low: bool) -> Status {
+        Status {
+            was_too_high,
+            was_too_low,
+            ..Status::default()
+        }
+    }
+
+    fn delays(assert: Persistence, deassert: Persistence) -> DebounceDelays {
+        DebounceDelays { assert, deassert }
+    }
+
+    #[test]
+    fn count_based_alarm() {
+        let high = delays(Persistence::Conversions(3), Persistence::Conversions(1));
+        let low = delays(Persistence::Conversions(1), Persistence::Conversions(1));
+        let mut debouncer = Debouncer::new(high, low);
+        assert_eq!(None, debouncer.update(&status(true, false), 0));
+        assert_eq!(None, debouncer.update(&status(true, false), 0));
+        let state = debouncer.update(&status(true, false), 0).unwrap();
+        assert!(state.high);
+        assert!(!state.low);
+        let state = debouncer.update(&status(false, false), 0).unwrap();
+        assert!(!state.high);
+    }
+
+    #[test]
+    fn interrupted_condition_restarts_delay() {
+        let low = delays(Persistence::Conversions(2), Persistence::Conversions(1));
+        let mut debouncer = Debouncer::new(low, low);
+        assert_eq!(None, debouncer.update(&status(false, true), 0));
+        assert_eq!(None, debouncer.update(&status(false, false), 0));
+        assert_eq!(None, debouncer.update(&status(false, true), 0));
+        assert!(debouncer.update(&status(false, true), 0).unwrap().low);
+    }
+
+    #[test]
+    fn time_based_alarm() {
+        let low = delays(
+            Persistence::Microseconds(60_000_000),
+            Persistence::Microseconds(10_000_000),
+        );
+        let mut debouncer = Debouncer::new(low, low);
+        assert_eq!(None, debouncer.update(&status(false, true), 1_000_000));
+        assert_eq!(None, debouncer.update(&status(false, true), 60_000_000));
+        assert!(
+            debouncer
+                .update(&status(false, true), 61_000_000)
+                .unwrap()
+                .low
+        );
+        assert_eq!(None, debouncer.update(&status(false, false), 62_000_000));
+        assert_eq!(None, debouncer.update(&status(false, false), 71_000_000));
+        assert!(
+            !debouncer
+                .update(&status(false, false), 72_000_000)
+                .unwrap()
+                .low
+        );
+        assert_eq!(AlarmState::default(), debouncer.state());
+    }
+}
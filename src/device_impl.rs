@@ -1,29 +1,37 @@
+use crate::measurement::{raw_to_lux, EXPONENT_MAX, MANTISSA_MAX};
 use crate::{
-    ic, marker, mode, ComparisonMode, Config, Error, FaultCount, IntegrationTime,
-    InterruptPinPolarity, LuxRange, Measurement, ModeChangeError, Opt300x, PhantomData, SlaveAddr,
-    Status,
+    ic, marker, mode, CalibrationError, Clock, ComparisonMode, Config, ConfigChange,
+    ConversionTimes, ConversionToken, DriverState, Error, Events, FaultCount, InFlightConfigPolicy,
+    IntegrationTime, InterruptPinPolarity, LuxRange, Measurement, MeasurementInfo, MeasurementMode,
+    ModeChangeError, Opt300x, PhantomData, PowerEstimate, SlaveAddr, Status,
 };
-use embedded_hal::i2c;
+use embedded_hal::{delay::DelayNs, i2c};
 
-struct Register;
+type RawMeasurement = Measurement<(u8, u16)>;
+
+/// Time between status polls during the conversion period calibration
+/// in microseconds
+const CALIBRATION_POLL_INTERVAL_US: u32 = 1_000;
+
+pub(crate) struct Register;
 impl Register {
     const RESULT: u8 = 0x00;
-    const CONFIG: u8 = 0x01;
-    const LOW_LIMIT: u8 = 0x02;
-    const HIGH_LIMIT: u8 = 0x03;
+    pub(crate) const CONFIG: u8 = 0x01;
+    pub(crate) const LOW_LIMIT: u8 = 0x02;
+    pub(crate) const HIGH_LIMIT: u8 = 0x03;
     const MANUFACTURER_ID: u8 = 0x7E;
     const DEVICE_ID: u8 = 0x7F;
 }
 
-struct BitFlags;
+pub(crate) struct BitFlags;
 impl BitFlags {
     const CT: u16 = 1 << 11;
-    const MODE1: u16 = 1 << 10;
-    const MODE0: u16 = 1 << 9;
-    const OVF: u16 = 1 << 8;
-    const CRF: u16 = 1 << 7;
-    const FH: u16 = 1 << 6;
-    const FL: u16 = 1 << 5;
+    pub(crate) const MODE1: u16 = 1 << 10;
+    pub(crate) const MODE0: u16 = 1 << 9;
+    pub(crate) const OVF: u16 = 1 << 8;
+    pub(crate) const CRF: u16 = 1 << 7;
+    pub(crate) const FH: u16 = 1 << 6;
+    pub(crate) const FL: u16 = 1 << 5;
     const L: u16 = 1 << 4;
     const POL: u16 = 1 << 3;
     const ME: u16 = 1 << 2;
@@ -35,6 +43,47 @@ impl Default for Config {
     }
 }
 
+impl Config {
+    /// Bits affecting the conversion results: range, conversion time,
+    /// mode and exponent masking
+    const CONVERSION_MASK: u16 =
+        0xF000 | BitFlags::CT | BitFlags::MODE1 | BitFlags::MODE0 | BitFlags::ME;
+
+    fn integration_time(self) -> IntegrationTime {
+        if (self.bits & BitFlags::CT) != 0 {
+            IntegrationTime::Ms800
+        } else {
+            IntegrationTime::Ms100
+        }
+    }
+
+    pub(crate) fn comparison_mode(self) -> ComparisonMode {
+        if (self.bits & BitFlags::L) != 0 {
+            ComparisonMode::LatchedWindow
+        } else {
+            ComparisonMode::TransparentHysteresis
+        }
+    }
+
+    fn lux_range(self) -> LuxRange {
+        match (self.bits >> 12) as u8 {
+            0b1100 => LuxRange::Auto,
+            rn => LuxRange::Manual(rn),
+        }
+    }
+
+    pub(crate) fn measurement_mode(self) -> MeasurementMode {
+        if (self.bits & BitFlags::MODE1) != 0 {
+            MeasurementMode::Continuous
+        } else {
+            MeasurementMode::OneShot
+        }
+    }
+}
+
+/// Power-up value of the high limit register
+const HIGH_LIMIT_DEFAULT: u16 = 0xBFFF;
+
 impl marker::WithDeviceId for ic::Opt3001 {}
 impl marker::WithDeviceId for ic::Opt3004 {}
 impl marker::WithDeviceId for ic::Opt3006 {}
@@ -45,15 +94,7 @@ macro_rules! create {
         impl<I2C> Opt300x<I2C, ic::$ic, mode::OneShot> {
             /// Create new instance of the device
             pub fn $method(i2c: I2C, address: SlaveAddr) -> Self {
-                Opt300x {
-                    i2c,
-                    address: address.addr(),
-                    config: Config::default(),
-                    low_limit: 0,
-                    was_conversion_started: false,
-                    _ic: PhantomData,
-                    _mode: PhantomData,
-                }
+                Opt300x::create(i2c, address.addr())
             }
         }
     };
@@ -66,14 +107,35 @@ create!(Opt3006, new_opt3006);
 impl<I2C> Opt300x<I2C, ic::Opt3007, mode::OneShot> {
     /// Create new instance of the OPT3007 device, which has a fixed I2C address.
     pub fn new_opt3007(i2c: I2C) -> Self {
+        Opt300x::create(i2c, 0b100_0101)
+    }
+}
+
+impl<I2C, IC> Opt300x<I2C, IC, mode::OneShot> {
+    fn create(i2c: I2C, address: u8) -> Self {
         Opt300x {
             i2c,
-            address: 0b100_0101,
-            config: Config::default(),
-            low_limit: 0,
-            was_conversion_started: false,
-            _ic: PhantomData,
-            _mode: PhantomData,
+            state: DriverState {
+                address,
+                config: Config::default(),
+                low_limit: 0,
+                high_limit: HIGH_LIMIT_DEFAULT,
+                end_of_conversion_mode: false,
+                was_conversion_started: false,
+                conversion_period_scale: None,
+                conversion_times: ConversionTimes::default(),
+                config_generation: 0,
+                conversion_generation: 0,
+                conversion_id: 0,
+                in_flight_config_policy: InFlightConfigPolicy::Restart,
+                pending_config: None,
+                last_config_change: None,
+                pending_events: Events::default(),
+                last_status: Status::default(),
+                conversion_ready_pending: false,
+                _ic: PhantomData,
+                _mode: PhantomData,
+            },
         }
     }
 }
@@ -83,6 +145,16 @@ impl<I2C, IC, MODE> Opt300x<I2C, IC, MODE> {
     pub fn destroy(self) -> I2C {
         self.i2c
     }
+
+    pub(crate) fn with_mode<NEWMODE>(mut self) -> Opt300x<I2C, IC, NEWMODE> {
+        self.state.was_conversion_started = false;
+        self.state.conversion_times = ConversionTimes::default();
+        self.state.conversion_ready_pending = false;
+        Opt300x {
+            i2c: self.i2c,
+            state: self.state.into_mode(),
+        }
+    }
 }
 
 impl<I2C, IC> Opt300x<I2C, IC, mode::OneShot>
@@ -96,22 +168,10 @@ where
     pub fn into_continuous(
         mut self,
     ) -> Result<Opt300x<I2C, IC, mode::Continuous>, ModeChangeError<I2C::Error, Self>> {
-        if let Err(Error::I2C(e)) = self.set_config(
-            self.config
-                .with_high(BitFlags::MODE0)
-                .with_high(BitFlags::MODE1),
-        ) {
+        if let Err(Error::I2C(e)) = self.apply_measurement_mode(MeasurementMode::Continuous) {
             return Err(ModeChangeError::I2C(e, self));
         }
-        Ok(Opt300x {
-            i2c: self.i2c,
-            address: self.address,
-            config: self.config,
-            low_limit: self.low_limit,
-            was_conversion_started: false,
-            _ic: PhantomData,
-            _mode: PhantomData,
-        })
+        Ok(self.with_mode())
     }
 }
 
@@ -125,22 +185,10 @@ where
     pub fn into_one_shot(
         mut self,
     ) -> Result<Opt300x<I2C, IC, mode::OneShot>, ModeChangeError<I2C::Error, Self>> {
-        if let Err(Error::I2C(e)) = self.set_config(
-            self.config
-                .with_low(BitFlags::MODE0)
-                .with_low(BitFlags::MODE1),
-        ) {
+        if let Err(Error::I2C(e)) = self.apply_measurement_mode(MeasurementMode::OneShot) {
             return Err(ModeChangeError::I2C(e, self));
         }
-        Ok(Opt300x {
-            i2c: self.i2c,
-            address: self.address,
-            config: self.config,
-            low_limit: self.low_limit,
-            was_conversion_started: false,
-            _ic: PhantomData,
-            _mode: PhantomData,
-        })
+        Ok(self.with_mode())
     }
 }
 
@@ -149,6 +197,10 @@ where
     I2C: i2c::I2c,
 {
     /// Read the result of the most recent light to digital conversion in lux
+    ///
+    /// Note that after changing the configuration this may still be the
+    /// result of a conversion with the previous configuration.
+    /// See [`wait_for_valid_conversion()`](Opt300x::wait_for_valid_conversion).
     pub fn read_lux(&mut self) -> Result<f32, Error<I2C::Error>> {
         let result = self.read_raw()?;
         Ok(raw_to_lux(result))
@@ -160,10 +212,212 @@ where
         let result = self.read_register(Register::RESULT)?;
         Ok(((result >> 12) as u8, result & 0xFFF))
     }
+
+    /// Read the result of the next light to digital conversion in lux
+    /// together with its status and information.
+    ///
+    /// This returns `nb::Error::WouldBlock` until a new conversion is ready.
+    ///
+    /// Note that the conversion ready flag is cleared automatically
+    /// after calling this method.
+    pub fn read_measurement(&mut self) -> nb::Result<Measurement<f32>, Error<I2C::Error>> {
+        self.read_next_measurement(None)
+    }
+
+    /// Read the result of the next light to digital conversion in lux
+    /// together with its status, information and completion time.
+    ///
+    /// This works like [`read_measurement()`](Opt300x::read_measurement)
+    /// and additionally estimates the time at which the conversion was
+    /// completed. The estimation is derived from the times at which the
+    /// conversion ready flag was observed, the end of the previous
+    /// conversion and the expected conversion period.
+    /// See [`conversion_period_us()`](Opt300x::conversion_period_us).
+    ///
+    /// Calling this method frequently improves the estimation.
+    ///
+    /// Note that the conversion ready flag is cleared automatically
+    /// after calling this method.
+    pub fn read_measurement_timestamped<CLK: Clock>(
+        &mut self,
+        clock: &mut CLK,
+    ) -> nb::Result<Measurement<f32>, Error<I2C::Error>> {
+        self.read_next_measurement(Some(clock))
+    }
+
+    /// Read the result of the next light to digital conversion which was
+    /// entirely performed with the current configuration.
+    ///
+    /// After changing the lux range, integration time, exponent masking or
+    /// measurement mode, the conversion in progress may still reflect the
+    /// previous configuration. This method discards such conversions and
+    /// returns `nb::Error::WouldBlock` until a valid conversion is ready.
+    /// See also [`Measurement::stale`].
+    ///
+    /// Note that the conversion ready flag is cleared automatically
+    /// after calling this method.
+    pub fn wait_for_valid_conversion(&mut self) -> nb::Result<Measurement<f32>, Error<I2C::Error>> {
+        let measurement = self.read_measurement()?;
+        if measurement.stale {
+            Err(nb::Error::WouldBlock)
+        } else {
+            Ok(measurement)
+        }
+    }
+}
+
+impl<I2C, IC> Opt300x<I2C, IC, mode::Continuous>
+where
+    I2C: i2c::I2c,
+{
+    /// Measure the actual conversion period using a monotonic clock.
+    ///
+    /// The internal oscillator of the device has some tolerance so the
+    /// actual conversion period deviates from the nominal integration time.
+    /// This measures the time between the conversion ready flag of
+    /// successive conversions over the given number of `conversions`
+    /// and returns the measured period in microseconds.
+    ///
+    /// The result is stored in the driver and used for subsequent timing
+    /// estimations, also after changing the integration time.
+    /// See [`conversion_period_us()`](Opt300x::conversion_period_us).
+    ///
+    /// This method blocks until `conversions + 1` conversions are ready,
+    /// polling the status every millisecond. A conversion ready flag already
+    /// set when starting is discarded since it is not known when it was set.
+    ///
+    /// Returns `CalibrationError::InvalidInputData` if `conversions` is zero
+    /// and `CalibrationError::Timeout` if a conversion is not ready within
+    /// twice the maximum conversion time.
+    /// See [`power_estimate()`](Opt300x::power_estimate).
+    ///
+    /// Note that the conversion ready flag is cleared automatically
+    /// after calling this method.
+    pub fn calibrate_conversion_period<CLK: Clock, D: DelayNs>(
+        &mut self,
+        clock: &mut CLK,
+        delay: &mut D,
+        conversions: u8,
+    ) -> Result<u32, CalibrationError<I2C::Error>> {
+        if conversions == 0 {
+            return Err(CalibrationError::InvalidInputData);
+        }
+        self.read_status().map_err(calibration_error)?;
+        self.state.conversion_ready_pending = false;
+        let start = self.wait_for_conversion_ready(clock, delay)?;
+        let mut end = start;
+        for _ in 0..conversions {
+            end = self.wait_for_conversion_ready(clock, delay)?;
+        }
+        let elapsed = end.saturating_sub(start);
+        let period = (elapsed / u64::from(conversions)) as u32;
+        self.state.conversion_period_scale =
+            Some(period as f32 / self.nominal_conversion_period_us() as f32);
+        Ok(period)
+    }
+
+    /// Wait for the conversion ready flag and return the time at which
+    /// it was observed
+    fn wait_for_conversion_ready<CLK: Clock, D: DelayNs>(
+        &mut self,
+        clock: &mut CLK,
+        delay: &mut D,
+    ) -> Result<u64, CalibrationError<I2C::Error>> {
+        let timeout = 2 * u64::from(self.power_estimate(0).max_conversion_time_us);
+        let started_at = clock.now_us();
+        loop {
+            let status = self.read_status().map_err(calibration_error)?;
+            let now = clock.now_us();
+            if status.conversion_ready {
+                return Ok(now);
+            }
+            if now.saturating_sub(started_at) >= timeout {
+                return Err(CalibrationError::Timeout);
+            }
+            delay.delay_us(CALIBRATION_POLL_INTERVAL_US);
+        }
+    }
 }
 
-fn raw_to_lux(result: (u8, u16)) -> f32 {
-    (f64::from(1 << result.0) * 0.01 * f64::from(result.1)) as f32
+fn calibration_error<E>(error: Error<E>) -> CalibrationError<E> {
+    match error {
+        Error::I2C(e) => CalibrationError::I2C(e),
+        Error::InvalidInputData => CalibrationError::InvalidInputData,
+    }
+}
+
+impl<I2C, IC, MODE> Opt300x<I2C, IC, MODE> {
+    /// Forget the conversion in progress and the observed status flags.
+    /// For example, after the device was powered down.
+    pub(crate) fn forget_conversion(&mut self) {
+        self.state.was_conversion_started = false;
+        self.state.conversion_times = ConversionTimes::default();
+        self.state.conversion_ready_pending = false;
+        self.state.last_status = Status::default();
+    }
+
+    fn measurement_info(&self, exponent: u8) -> MeasurementInfo {
+        MeasurementInfo {
+            exponent,
+            integration_time: self.state.config.integration_time(),
+        }
+    }
+
+    fn conversion_ready_at(&mut self, now: Option<u64>) -> Option<u64> {
+        let period = self.conversion_period_us();
+        now.map(|now| self.state.conversion_times.ready(now, period))
+    }
+
+    fn is_conversion_stale(&self) -> bool {
+        self.state.conversion_generation != self.state.config_generation
+    }
+
+    /// Configuration including the changes deferred until the end of the
+    /// conversion in progress
+    pub(crate) fn desired_config(&self) -> Config {
+        self.state.pending_config.unwrap_or(self.state.config)
+    }
+
+    /// Update the cached configuration after writing it to the device.
+    ///
+    /// Writing the configuration clears the conversion ready flag, so a flag
+    /// observed before belongs to a result which is no longer relevant.
+    fn update_config(&mut self, config: Config) {
+        if (self.state.config.bits ^ config.bits) & Config::CONVERSION_MASK != 0 {
+            self.state.config_generation = self.state.config_generation.wrapping_add(1);
+        }
+        self.state.config = config;
+        self.state.pending_config = None;
+        self.state.conversion_ready_pending = false;
+    }
+}
+
+impl<I2C, IC, MODE> Opt300x<I2C, IC, MODE> {
+    /// Set the behavior when changing the configuration while a one-shot
+    /// conversion is in progress.
+    ///
+    /// Writing the configuration register during a one-shot conversion would
+    /// otherwise abort it. See [`InFlightConfigPolicy`].
+    pub fn set_in_flight_config_policy(&mut self, policy: InFlightConfigPolicy) {
+        self.state.in_flight_config_policy = policy;
+    }
+
+    /// Get what happened during the last configuration change.
+    ///
+    /// Returns `None` if the configuration has not been changed.
+    pub fn last_config_change(&self) -> Option<ConfigChange> {
+        self.state.last_config_change
+    }
+
+    /// Get the configuration generation.
+    ///
+    /// This is a wrapping counter increased every time the lux range,
+    /// integration time, exponent masking or measurement mode is changed.
+    /// Measurements from conversions performed across a change are flagged
+    /// as stale. See [`Measurement::stale`].
+    pub fn config_generation(&self) -> u16 {
+        self.state.config_generation
+    }
 }
 
 impl<I2C, IC> Opt300x<I2C, IC, mode::OneShot>
@@ -173,37 +427,232 @@ where
     /// Read the result of the most recent light to digital conversion in lux
     pub fn read_lux(&mut self) -> nb::Result<Measurement<f32>, Error<I2C::Error>> {
         let measurement = self.read_raw()?;
-        Ok(Measurement {
-            result: raw_to_lux(measurement.result),
-            status: measurement.status,
-        })
+        Ok(to_lux_measurement(measurement))
     }
 
     /// Read the result of the most recent light to digital conversion in
     /// raw format: (exponent, mantissa)
     pub fn read_raw(&mut self) -> nb::Result<Measurement<(u8, u16)>, Error<I2C::Error>> {
-        if self.was_conversion_started {
-            let status = self.read_status().map_err(nb::Error::Other)?;
-            if status.conversion_ready {
-                let result = self
-                    .read_register(Register::RESULT)
-                    .map_err(nb::Error::Other)?;
-                self.was_conversion_started = false;
-                Ok(Measurement {
-                    result: ((result >> 12) as u8, result & 0xFFF),
-                    status,
-                })
-            } else {
-                Err(nb::Error::WouldBlock)
+        self.read_one_shot(None)
+    }
+
+    /// Read the result of the most recent light to digital conversion in lux
+    /// together with the estimated time at which the conversion was completed.
+    ///
+    /// This works like [`read_lux()`](Opt300x::read_lux). The completion time
+    /// is estimated from the time at which the conversion was started, the
+    /// expected conversion period and the times at which the conversion
+    /// ready flag was observed. For a conversion restarted by a configuration
+    /// change only the observations are available, which is less precise.
+    /// See [`conversion_period_us()`](Opt300x::conversion_period_us) and
+    /// [`InFlightConfigPolicy::Restart`].
+    pub fn read_lux_timestamped<CLK: Clock>(
+        &mut self,
+        clock: &mut CLK,
+    ) -> nb::Result<Measurement<f32>, Error<I2C::Error>> {
+        let measurement = self.read_raw_timestamped(clock)?;
+        Ok(to_lux_measurement(measurement))
+    }
+
+    /// Read the result of the most recent light to digital conversion in
+    /// raw format: (exponent, mantissa) together with the estimated time
+    /// at which the conversion was completed.
+    ///
+    /// See [`read_lux_timestamped()`](Opt300x::read_lux_timestamped).
+    pub fn read_raw_timestamped<CLK: Clock>(
+        &mut self,
+        clock: &mut CLK,
+    ) -> nb::Result<Measurement<(u8, u16)>, Error<I2C::Error>> {
+        self.read_one_shot(Some(clock))
+    }
+
+    /// Start a one-shot conversion.
+    ///
+    /// Returns a token which can be used to poll the result of the conversion.
+    /// See [`poll()`](Opt300x::poll).
+    ///
+    /// If a conversion was already in progress it is restarted and the
+    /// previous token becomes invalid.
+    ///
+    /// This can be used instead of the `nb` methods like
+    /// [`read_lux()`](Opt300x::read_lux) to control exactly when the bus
+    /// is accessed.
+    pub fn start_conversion(&mut self) -> Result<ConversionToken, Error<I2C::Error>> {
+        self.start_one_shot(None)?;
+        Ok(ConversionToken {
+            id: self.state.conversion_id,
+        })
+    }
+
+    /// Poll the result of a one-shot conversion in lux.
+    ///
+    /// Returns `None` if the conversion is not ready yet.
+    ///
+    /// Returns `Error::InvalidInputData` if the token does not belong
+    /// to the conversion in progress. For example, if the result of the
+    /// conversion was already returned.
+    ///
+    /// Note that the conversion ready flag is cleared automatically
+    /// after calling this method.
+    pub fn poll(
+        &mut self,
+        token: &ConversionToken,
+    ) -> Result<Option<Measurement<f32>>, Error<I2C::Error>> {
+        if !self.state.was_conversion_started || token.id != self.state.conversion_id {
+            return Err(Error::InvalidInputData);
+        }
+        let measurement = self.poll_one_shot(None)?;
+        Ok(measurement.map(to_lux_measurement))
+    }
+}
+
+impl<I2C, IC, MODE> Opt300x<I2C, IC, MODE>
+where
+    I2C: i2c::I2c,
+{
+    /// Write the measurement mode and forget the conversion in progress
+    pub(crate) fn apply_measurement_mode(
+        &mut self,
+        mode: MeasurementMode,
+    ) -> Result<(), Error<I2C::Error>> {
+        self.apply_config_in_mode(self.desired_config(), mode)
+    }
+
+    /// Write the configuration with the given measurement mode and forget
+    /// the conversion in progress
+    pub(crate) fn apply_config_in_mode(
+        &mut self,
+        config: Config,
+        mode: MeasurementMode,
+    ) -> Result<(), Error<I2C::Error>> {
+        let config = match mode {
+            MeasurementMode::OneShot => config.with_low(BitFlags::MODE0).with_low(BitFlags::MODE1),
+            MeasurementMode::Continuous => {
+                config.with_high(BitFlags::MODE0).with_high(BitFlags::MODE1)
+            }
+        };
+        self.apply_config(config)?;
+        self.state.was_conversion_started = false;
+        self.state.conversion_times = ConversionTimes::default();
+        Ok(())
+    }
+
+    pub(crate) fn read_next_measurement(
+        &mut self,
+        clock: Option<&mut dyn Clock>,
+    ) -> nb::Result<Measurement<f32>, Error<I2C::Error>> {
+        let status = self.read_conversion_status().map_err(nb::Error::Other)?;
+        let now = clock.map(|clock| clock.now_us());
+        if !status.conversion_ready {
+            if let Some(now) = now {
+                self.state.conversion_times.not_ready(now);
+            }
+            return Err(nb::Error::WouldBlock);
+        }
+        let timestamp = self.conversion_ready_at(now);
+        let result = self
+            .read_register(Register::RESULT)
+            .map_err(nb::Error::Other)?;
+        let result = ((result >> 12) as u8, result & 0xFFF);
+        let stale = self.is_conversion_stale();
+        self.state.conversion_generation = self.state.config_generation;
+        Ok(Measurement {
+            result: raw_to_lux(result),
+            status,
+            info: self.measurement_info(result.0),
+            timestamp,
+            stale,
+        })
+    }
+
+    pub(crate) fn read_one_shot(
+        &mut self,
+        clock: Option<&mut dyn Clock>,
+    ) -> nb::Result<Measurement<(u8, u16)>, Error<I2C::Error>> {
+        if self.state.was_conversion_started {
+            match self.poll_one_shot(clock).map_err(nb::Error::Other)? {
+                Some(measurement) => Ok(measurement),
+                None => Err(nb::Error::WouldBlock),
             }
         } else {
-            let config = self.config.with_high(BitFlags::MODE0);
-            self.write_register(Register::CONFIG, config.bits)
-                .map_err(nb::Error::Other)?;
-            self.was_conversion_started = true;
+            self.start_one_shot(clock).map_err(nb::Error::Other)?;
             Err(nb::Error::WouldBlock)
         }
     }
+
+    fn start_one_shot(&mut self, clock: Option<&mut dyn Clock>) -> Result<(), Error<I2C::Error>> {
+        let config = self.desired_config();
+        self.write_register(Register::CONFIG, config.with_high(BitFlags::MODE0).bits)?;
+        self.update_config(config);
+        self.state.was_conversion_started = true;
+        self.state.conversion_id = self.state.conversion_id.wrapping_add(1);
+        self.state.conversion_generation = self.state.config_generation;
+        self.state.conversion_times = ConversionTimes::default();
+        if let Some(clock) = clock {
+            self.state.conversion_times.started(clock.now_us());
+        }
+        Ok(())
+    }
+
+    /// Abort the conversion in progress, if any, and make sure that the
+    /// device is shut down.
+    pub(crate) fn shut_down(&mut self) -> Result<(), Error<I2C::Error>> {
+        self.state.was_conversion_started = false;
+        let config = self.desired_config();
+        self.write_register(Register::CONFIG, config.bits)?;
+        self.update_config(config);
+        Ok(())
+    }
+
+    fn poll_one_shot(
+        &mut self,
+        clock: Option<&mut dyn Clock>,
+    ) -> Result<Option<RawMeasurement>, Error<I2C::Error>> {
+        let status = self.read_conversion_status()?;
+        let now = clock.map(|clock| clock.now_us());
+        if !status.conversion_ready {
+            if let Some(now) = now {
+                self.state.conversion_times.not_ready(now);
+            }
+            return Ok(None);
+        }
+        let timestamp = self.conversion_ready_at(now);
+        let result = self.read_register(Register::RESULT)?;
+        self.state.was_conversion_started = false;
+        let exponent = (result >> 12) as u8;
+        let measurement = Measurement {
+            result: (exponent, result & 0xFFF),
+            status,
+            info: self.measurement_info(exponent),
+            timestamp,
+            stale: self.is_conversion_stale(),
+        };
+        if let Some(config) = self.state.pending_config {
+            // If this fails the configuration is applied when
+            // starting the next conversion.
+            let _ = self.apply_config(config);
+        }
+        Ok(Some(measurement))
+    }
+}
+
+fn status_from_config(config: u16) -> Status {
+    Status {
+        has_overflown: (config & BitFlags::OVF) != 0,
+        conversion_ready: (config & BitFlags::CRF) != 0,
+        was_too_high: (config & BitFlags::FH) != 0,
+        was_too_low: (config & BitFlags::FL) != 0,
+    }
+}
+
+pub(crate) fn to_lux_measurement(measurement: Measurement<(u8, u16)>) -> Measurement<f32> {
+    Measurement {
+        result: raw_to_lux(measurement.result),
+        status: measurement.status,
+        info: measurement.info,
+        timestamp: measurement.timestamp,
+        stale: measurement.stale,
+    }
 }
 
 impl<I2C, IC, MODE> Opt300x<I2C, IC, MODE>
@@ -216,12 +665,32 @@ where
     /// after calling this method.
     pub fn read_status(&mut self) -> Result<Status, Error<I2C::Error>> {
         let config = self.read_register(Register::CONFIG)?;
-        Ok(Status {
-            has_overflown: (config & BitFlags::OVF) != 0,
-            conversion_ready: (config & BitFlags::CRF) != 0,
-            was_too_high: (config & BitFlags::FH) != 0,
-            was_too_low: (config & BitFlags::FL) != 0,
-        })
+        let status = status_from_config(config);
+        self.record_events(status);
+        Ok(status)
+    }
+
+    /// Read the configuration register.
+    ///
+    /// The status flags are recorded and a conversion ready flag is not lost
+    /// for the measurement reading methods.
+    pub(crate) fn read_config_register(&mut self) -> Result<u16, Error<I2C::Error>> {
+        let config = self.read_register(Register::CONFIG)?;
+        let status = status_from_config(config);
+        self.record_events(status);
+        if status.conversion_ready {
+            self.state.conversion_ready_pending = true;
+        }
+        Ok(config)
+    }
+
+    /// Read the status including a conversion ready flag observed
+    /// while reading the events.
+    fn read_conversion_status(&mut self) -> Result<Status, Error<I2C::Error>> {
+        let mut status = self.read_status()?;
+        status.conversion_ready |= self.state.conversion_ready_pending;
+        self.state.conversion_ready_pending = false;
+        Ok(status)
     }
 }
 
@@ -234,7 +703,7 @@ where
     /// Note that the conversion ready flag is cleared automatically
     /// after calling this method.
     pub fn set_fault_count(&mut self, count: FaultCount) -> Result<(), Error<I2C::Error>> {
-        let config = self.config.bits & !0b11;
+        let config = self.desired_config().bits & !0b11;
         let config = match count {
             FaultCount::One => config,
             FaultCount::Two => config | 0b01,
@@ -257,7 +726,7 @@ where
             LuxRange::Manual(rn) if rn >= 0b1100 => Err(Error::InvalidInputData),
             LuxRange::Manual(rn) => Ok(rn),
         }?;
-        let config = self.config.bits & 0x0FFF;
+        let config = self.desired_config().bits & 0x0FFF;
         self.set_config(Config {
             bits: config | (u16::from(value) << 12),
         })
@@ -269,8 +738,8 @@ where
     /// after calling this method.
     pub fn set_integration_time(&mut self, time: IntegrationTime) -> Result<(), Error<I2C::Error>> {
         let config = match time {
-            IntegrationTime::Ms100 => self.config.with_low(BitFlags::CT),
-            IntegrationTime::Ms800 => self.config.with_high(BitFlags::CT),
+            IntegrationTime::Ms100 => self.desired_config().with_low(BitFlags::CT),
+            IntegrationTime::Ms800 => self.desired_config().with_high(BitFlags::CT),
         };
         self.set_config(config)
     }
@@ -284,8 +753,8 @@ where
         polarity: InterruptPinPolarity,
     ) -> Result<(), Error<I2C::Error>> {
         let config = match polarity {
-            InterruptPinPolarity::Low => self.config.with_low(BitFlags::POL),
-            InterruptPinPolarity::High => self.config.with_high(BitFlags::POL),
+            InterruptPinPolarity::Low => self.desired_config().with_low(BitFlags::POL),
+            InterruptPinPolarity::High => self.desired_config().with_high(BitFlags::POL),
         };
         self.set_config(config)
     }
@@ -295,7 +764,7 @@ where
     /// Note that the conversion ready flag is cleared automatically
     /// after calling this method.
     pub fn enable_exponent_masking(&mut self) -> Result<(), Error<I2C::Error>> {
-        self.set_config(self.config.with_high(BitFlags::ME))
+        self.set_config(self.desired_config().with_high(BitFlags::ME))
     }
 
     /// Disable exponent masking (default).
@@ -303,7 +772,7 @@ where
     /// Note that the conversion ready flag is cleared automatically
     /// after calling this method.
     pub fn disable_exponent_masking(&mut self) -> Result<(), Error<I2C::Error>> {
-        self.set_config(self.config.with_low(BitFlags::ME))
+        self.set_config(self.desired_config().with_low(BitFlags::ME))
     }
 
     /// Set result comparison mode for interrupt reporting
@@ -312,8 +781,8 @@ where
     /// after calling this method.
     pub fn set_comparison_mode(&mut self, mode: ComparisonMode) -> Result<(), Error<I2C::Error>> {
         let config = match mode {
-            ComparisonMode::LatchedWindow => self.config.with_high(BitFlags::L),
-            ComparisonMode::TransparentHysteresis => self.config.with_low(BitFlags::L),
+            ComparisonMode::LatchedWindow => self.desired_config().with_high(BitFlags::L),
+            ComparisonMode::TransparentHysteresis => self.desired_config().with_low(BitFlags::L),
         };
         self.set_config(config)
     }
@@ -324,20 +793,36 @@ where
     /// 11 or a mantissa value greater than 4095.
     ///
     /// Note that this disables the end-of-conversion mode.
+    /// See [`set_low_limit_raw_keeping_end_of_conversion_mode()`](Opt300x::set_low_limit_raw_keeping_end_of_conversion_mode).
     pub fn set_low_limit_raw(
         &mut self,
         exponent: u8,
         mantissa: u16,
     ) -> Result<(), Error<I2C::Error>> {
-        if exponent > 0b1011 || mantissa > 0xFFF {
-            return Err(Error::InvalidInputData);
-        }
-        let limit = u16::from(exponent) << 12 | mantissa;
+        let limit = raw_limit(exponent, mantissa)?;
         self.write_register(Register::LOW_LIMIT, limit)?;
-        self.low_limit = limit;
+        self.state.low_limit = limit;
+        self.state.end_of_conversion_mode = false;
         Ok(())
     }
 
+    /// Set the lux low limit in raw format (exponent, mantissa) without
+    /// changing the end-of-conversion mode.
+    ///
+    /// While the end-of-conversion mode is enabled, the new limit is stored
+    /// and takes effect once the end-of-conversion mode is disabled.
+    ///
+    /// Returns `Error::InvalidInputData` for an exponent value greater than
+    /// 11 or a mantissa value greater than 4095.
+    pub fn set_low_limit_raw_keeping_end_of_conversion_mode(
+        &mut self,
+        exponent: u8,
+        mantissa: u16,
+    ) -> Result<(), Error<I2C::Error>> {
+        let limit = raw_limit(exponent, mantissa)?;
+        self.write_low_limit(limit, self.state.end_of_conversion_mode)
+    }
+
     /// Set the lux high limit in raw format (exponent, mantissa).
     ///
     /// Returns `Error::InvalidInputData` for an exponent value greater than
@@ -347,11 +832,10 @@ where
         exponent: u8,
         mantissa: u16,
     ) -> Result<(), Error<I2C::Error>> {
-        if exponent > 0b1011 || mantissa > 0xFFF {
-            return Err(Error::InvalidInputData);
-        }
-        let limit = u16::from(exponent) << 12 | mantissa;
-        self.write_register(Register::HIGH_LIMIT, limit)
+        let limit = raw_limit(exponent, mantissa)?;
+        self.write_register(Register::HIGH_LIMIT, limit)?;
+        self.state.high_limit = limit;
+        Ok(())
     }
 
     /// Enable end-of-conversion mode
@@ -359,8 +843,7 @@ where
     /// Note that this changes the two highest bits of the lux low limit exponent.
     /// Please see the device datasheet for further details.
     pub fn enable_end_of_conversion_mode(&mut self) -> Result<(), Error<I2C::Error>> {
-        let limit = self.low_limit | 0b1100 << 12;
-        self.write_register(Register::LOW_LIMIT, limit)
+        self.write_low_limit(self.state.low_limit, true)
     }
 
     /// Disable end-of-conversion mode
@@ -369,10 +852,60 @@ where
     /// exponent to the last value set before enabling the end-of-conversion
     /// mode (0b00 by default).
     pub fn disable_end_of_conversion_mode(&mut self) -> Result<(), Error<I2C::Error>> {
-        self.write_register(Register::LOW_LIMIT, self.low_limit)
+        self.write_low_limit(self.state.low_limit, false)
+    }
+
+    pub(crate) fn write_low_limit(
+        &mut self,
+        limit: u16,
+        end_of_conversion_mode: bool,
+    ) -> Result<(), Error<I2C::Error>> {
+        let value = if end_of_conversion_mode {
+            limit | 0b1100 << 12
+        } else {
+            limit
+        };
+        self.write_register(Register::LOW_LIMIT, value)?;
+        self.state.low_limit = limit;
+        self.state.end_of_conversion_mode = end_of_conversion_mode;
+        Ok(())
     }
 }
 
+impl<I2C, IC, MODE> Opt300x<I2C, IC, MODE> {
+    /// Get the lux low limit in raw format (exponent, mantissa) as last set
+    /// through this driver.
+    ///
+    /// This does not include the end-of-conversion mode bits.
+    pub fn low_limit_raw(&self) -> (u8, u16) {
+        (
+            (self.state.low_limit >> 12) as u8,
+            self.state.low_limit & 0xFFF,
+        )
+    }
+
+    /// Get the lux high limit in raw format (exponent, mantissa) as last set
+    /// through this driver.
+    pub fn high_limit_raw(&self) -> (u8, u16) {
+        (
+            (self.state.high_limit >> 12) as u8,
+            self.state.high_limit & 0xFFF,
+        )
+    }
+
+    /// Get whether the end-of-conversion mode is enabled.
+    pub fn is_end_of_conversion_mode_enabled(&self) -> bool {
+        self.state.end_of_conversion_mode
+    }
+}
+
+fn raw_limit<E>(exponent: u8, mantissa: u16) -> Result<u16, Error<E>> {
+    if exponent > EXPONENT_MAX || mantissa > MANTISSA_MAX {
+        return Err(Error::InvalidInputData);
+    }
+    Ok(u16::from(exponent) << 12 | mantissa)
+}
+
 impl<I2C, IC, MODE> Opt300x<I2C, IC, MODE>
 where
     I2C: i2c::I2c,
@@ -394,22 +927,72 @@ where
     }
 }
 
+impl<I2C, IC, MODE> Opt300x<I2C, IC, MODE> {
+    /// Estimate the conversion timing and average supply current for the
+    /// current configuration.
+    ///
+    /// `sampling_interval_us` is the time between one-shot measurements
+    /// in microseconds. It is ignored in continuous mode.
+    /// See [`PowerEstimate::new()`] for further details.
+    pub fn power_estimate(&self, sampling_interval_us: u32) -> PowerEstimate {
+        PowerEstimate::new(
+            self.state.config.integration_time(),
+            self.state.config.lux_range(),
+            self.state.config.measurement_mode(),
+            sampling_interval_us,
+        )
+    }
+}
+
+impl<I2C, IC, MODE> Opt300x<I2C, IC, MODE> {
+    /// Get the expected conversion period in microseconds for the
+    /// current configuration.
+    ///
+    /// This is the nominal conversion time corrected with the result of
+    /// the last conversion period calibration, if any.
+    /// See [`calibrate_conversion_period()`](Opt300x::calibrate_conversion_period).
+    pub fn conversion_period_us(&self) -> u32 {
+        let nominal = self.nominal_conversion_period_us();
+        match self.state.conversion_period_scale {
+            Some(scale) => (nominal as f32 * scale) as u32,
+            None => nominal,
+        }
+    }
+
+    fn nominal_conversion_period_us(&self) -> u32 {
+        self.power_estimate(0).conversion_time_us
+    }
+}
+
 impl<I2C, IC, MODE> Opt300x<I2C, IC, MODE> {
     /// Reset the internal state of this driver to the default values.
     ///
     /// *Note:* This does not alter the state or configuration of the device.
     ///
-    /// This resets the cached configuration register value in this driver to
-    /// the power-up (reset) configuration of the device.
+    /// This resets the cached configuration register, limits and
+    /// end-of-conversion mode values in this driver to the power-up (reset)
+    /// configuration of the device.
+    ///
+    /// The conversion period calibration is kept since it does not depend
+    /// on the device configuration.
     ///
     /// This needs to be called after performing a reset on the device, for
     /// example through an I2C general-call Reset command, which was not done
     /// through this driver to ensure that the configurations in the device
     /// and in the driver match.
     pub fn reset_internal_driver_state(&mut self) {
-        self.config = Config::default();
-        self.low_limit = 0;
-        self.was_conversion_started = false;
+        self.state.config = Config::default();
+        self.state.low_limit = 0;
+        self.state.high_limit = HIGH_LIMIT_DEFAULT;
+        self.state.end_of_conversion_mode = false;
+        self.state.was_conversion_started = false;
+        self.state.conversion_times = ConversionTimes::default();
+        self.state.config_generation = self.state.config_generation.wrapping_add(1);
+        self.state.pending_config = None;
+        self.state.last_config_change = None;
+        self.state.pending_events = Events::default();
+        self.state.last_status = Status::default();
+        self.state.conversion_ready_pending = false;
     }
 }
 
@@ -417,10 +1000,10 @@ impl<I2C, IC, MODE> Opt300x<I2C, IC, MODE>
 where
     I2C: i2c::I2c,
 {
-    fn read_register(&mut self, register: u8) -> Result<u16, Error<I2C::Error>> {
+    pub(crate) fn read_register(&mut self, register: u8) -> Result<u16, Error<I2C::Error>> {
         let mut data = [0, 0];
         self.i2c
-            .write_read(self.address, &[register], &mut data)
+            .write_read(self.state.address, &[register], &mut data)
             .map_err(Error::I2C)
             .and(Ok(u16::from(data[0]) << 8 | u16::from(data[1])))
     }
@@ -431,14 +1014,54 @@ where
     I2C: i2c::I2c,
 {
     fn set_config(&mut self, config: Config) -> Result<(), Error<I2C::Error>> {
+        if !self.state.was_conversion_started {
+            self.apply_config(config)?;
+            self.state.last_config_change = Some(ConfigChange::Applied);
+            return Ok(());
+        }
+        match self.state.in_flight_config_policy {
+            InFlightConfigPolicy::Restart => {
+                self.write_register(Register::CONFIG, config.with_high(BitFlags::MODE0).bits)?;
+                self.update_config(config);
+                self.state.conversion_generation = self.state.config_generation;
+                self.state.conversion_times = ConversionTimes::default();
+                self.state.last_config_change = Some(ConfigChange::ConversionRestarted);
+            }
+            InFlightConfigPolicy::Defer => {
+                self.state.pending_config = Some(config);
+                self.state.last_config_change = Some(ConfigChange::Deferred);
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the cached limits and configuration to the device.
+    ///
+    /// A conversion in progress is forgotten.
+    pub(crate) fn restore_registers(&mut self) -> Result<(), Error<I2C::Error>> {
+        self.forget_conversion();
+        self.write_low_limit(self.state.low_limit, self.state.end_of_conversion_mode)?;
+        self.write_register(Register::HIGH_LIMIT, self.state.high_limit)?;
+        self.apply_config(self.desired_config())?;
+        self.state.conversion_generation = self.state.config_generation;
+        Ok(())
+    }
+
+    pub(crate) fn apply_config(&mut self, config: Config) -> Result<(), Error<I2C::Error>> {
         self.write_register(Register::CONFIG, config.bits)?;
-        self.config = config;
+        self.update_config(config);
         Ok(())
     }
 
-    fn write_register(&mut self, register: u8, value: u16) -> Result<(), Error<I2C::Error>> {
+    pub(crate) fn write_register(
+        &mut self,
+        register: u8,
+        value: u16,
+    ) -> Result<(), Error<I2C::Error>> {
         let data = [register, (value >> 8) as u8, value as u8];
-        self.i2c.write(self.address, &data).map_err(Error::I2C)
+        self.i2c
+            .write(self.state.address, &data)
+            .map_err(Error::I2C)
     }
 }
 
@@ -463,15 +1086,52 @@ mod tests {
         }
     }
 
+    #[test]
+    fn power_estimate_follows_configuration() {
+        let mut device = Opt300x::new_opt3001(I2cMock {}, SlaveAddr::default());
+        device.set_integration_time(IntegrationTime::Ms100).unwrap();
+        device.set_lux_range(LuxRange::Manual(1)).unwrap();
+        let one_shot = device.power_estimate(1_000_000);
+        assert_eq!(100_000, one_shot.conversion_time_us);
+        let device = device.into_continuous().ok().unwrap();
+        let continuous = device.power_estimate(1_000_000);
+        assert_eq!(100_000, continuous.conversion_time_us);
+        assert!(continuous.average_current_ua > one_shot.average_current_ua);
+    }
+
+    #[test]
+    fn only_conversion_changes_increase_config_generation() {
+        let mut device = Opt300x::new_opt3001(I2cMock {}, SlaveAddr::default());
+        device.set_fault_count(FaultCount::Eight).unwrap();
+        device
+            .set_comparison_mode(ComparisonMode::LatchedWindow)
+            .unwrap();
+        assert_eq!(0, device.config_generation());
+        device.set_lux_range(LuxRange::Manual(1)).unwrap();
+        device.set_lux_range(LuxRange::Manual(1)).unwrap();
+        assert_eq!(1, device.config_generation());
+        device.set_integration_time(IntegrationTime::Ms100).unwrap();
+        device.enable_exponent_masking().unwrap();
+        assert_eq!(3, device.config_generation());
+        let device = device.into_continuous().ok().unwrap();
+        assert_eq!(4, device.config_generation());
+    }
+
     #[test]
     fn can_reset_driver_state() {
         let mut device = Opt300x::new_opt3001(I2cMock {}, SlaveAddr::default());
         device.set_fault_count(FaultCount::Eight).unwrap();
         device.set_low_limit_raw(1, 2).unwrap();
-        assert_ne!(device.config, Config::default());
-        assert_ne!(device.low_limit, 0);
+        device.set_high_limit_raw(3, 4).unwrap();
+        device.enable_end_of_conversion_mode().unwrap();
+        assert_ne!(device.state.config, Config::default());
+        assert_ne!(device.state.low_limit, 0);
+        assert_ne!(device.state.high_limit, HIGH_LIMIT_DEFAULT);
+        assert!(device.state.end_of_conversion_mode);
         device.reset_internal_driver_state();
-        assert_eq!(device.config, Config::default());
-        assert_eq!(device.low_limit, 0);
+        assert_eq!(device.state.config, Config::default());
+        assert_eq!(device.state.low_limit, 0);
+        assert_eq!(device.state.high_limit, HIGH_LIMIT_DEFAULT);
+        assert!(!device.state.end_of_conversion_mode);
     }
 }
@@ -0,0 +1,192 @@
+//! Threshold events implementation
+use crate::{ComparisonMode, Error, Event, Events, Opt300x, Status};
+use embedded_hal::i2c;
+
+const EVENTS: [Event; 4] = [
+    Event::RoseAboveHigh,
+    Event::FellBelowLow,
+    Event::ConversionReady,
+    Event::Overflow,
+];
+
+const THRESHOLD_EVENTS: [Event; 2] = [Event::RoseAboveHigh, Event::FellBelowLow];
+
+impl Event {
+    fn bit(self) -> u8 {
+        match self {
+            Event::RoseAboveHigh => 1,
+            Event::FellBelowLow => 1 << 1,
+            Event::ConversionReady => 1 << 2,
+            Event::Overflow => 1 << 3,
+        }
+    }
+}
+
+impl Events {
+    /// Whether the set contains an event.
+    pub fn contains(&self, event: Event) -> bool {
+        (self.bits & event.bit()) != 0
+    }
+
+    /// Whether the set contains no events.
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    /// Iterate over the events in the set.
+    pub fn iter(&self) -> impl Iterator<Item = Event> {
+        let events = *self;
+        EVENTS.iter().copied().filter(move |e| events.contains(*e))
+    }
+
+    /// Add an event to the set.
+    pub fn insert(&mut self, event: Event) {
+        self.bits |= event.bit();
+    }
+
+    /// Remove an event from the set.
+    pub fn remove(&mut self, event: Event) {
+        self.bits &= !event.bit();
+    }
+
+    /// Create a set from its bit representation.
+    ///
+    /// Returns `None` if unknown bits are set.
+    pub(crate) fn from_bits(bits: u8) -> Option<Self> {
+        let known = EVENTS.iter().fold(0, |known, event| known | event.bit());
+        if bits & !known == 0 {
+            Some(Events { bits })
+        } else {
+            None
+        }
+    }
+
+    fn remove_all(&mut self, other: Events) {
+        self.bits &= !other.bits;
+    }
+
+    fn insert_all(&mut self, other: Events) {
+        self.bits |= other.bits;
+    }
+}
+
+impl From<Event> for Events {
+    fn from(event: Event) -> Self {
+        Events { bits: event.bit() }
+    }
+}
+
+impl<I2C, IC, MODE> Opt300x<I2C, IC, MODE>
+where
+    I2C: i2c::I2c,
+{
+    /// Read the pending events.
+    ///
+    /// The flag high and flag low fields are interpreted according to the
+    /// current comparison mode:
+    /// - Latched window-style: `RoseAboveHigh` and `FellBelowLow` are reported
+    ///   whenever the respective flag is set.
+    /// - Transparent hysteresis-style: `RoseAboveHigh` is reported when the
+    ///   flag high field gets set and `FellBelowLow` when it gets cleared again
+    ///   or when the flag low field gets set.
+    ///
+    /// Reading the status clears the hardware flags and releases a latched
+    /// interrupt. The events remain pending in the driver until they are
+    /// acknowledged with [`acknowledge_events()`](Opt300x::acknowledge_events).
+    ///
+    /// A `ConversionReady` event observed here is not lost for the
+    /// measurement reading methods, unless the configuration is written
+    /// in between since that invalidates the result.
+    pub fn read_events(&mut self) -> Result<Events, Error<I2C::Error>> {
+        let status = self.read_status()?;
+        if status.conversion_ready {
+            self.state.conversion_ready_pending = true;
+        }
+        Ok(self.state.pending_events)
+    }
+
+    /// Get the events pending acknowledgment without communicating with the device.
+    pub fn pending_events(&self) -> Events {
+        self.state.pending_events
+    }
+
+    /// Acknowledge some pending events.
+    ///
+    /// The acknowledged events are removed from the pending events.
+    pub fn acknowledge_events(&mut self, events: Events) {
+        self.state.pending_events.remove_all(events);
+    }
+
+    /// Read the events and return the pending threshold event, if any.
+    pub(crate) fn read_threshold_event(&mut self) -> Result<Option<Event>, Error<I2C::Error>> {
+        let events = self.read_events()?;
+        Ok(THRESHOLD_EVENTS
+            .iter()
+            .copied()
+            .find(|event| events.contains(*event)))
+    }
+
+    /// Acknowledge the pending threshold events.
+    pub(crate) fn acknowledge_threshold_events(&mut self) {
+        for event in THRESHOLD_EVENTS.iter() {
+            self.state.pending_events.remove(*event);
+        }
+    }
+
+    /// Record the events corresponding to a status read from the device.
+    pub(crate) fn record_events(&mut self, status: Status) {
+        let previous = self.state.last_status;
+        let mut events = Events::default();
+        match self.state.config.comparison_mode() {
+            ComparisonMode::LatchedWindow => {
+                if status.was_too_high {
+                    events.insert(Event::RoseAboveHigh);
+                }
+                if status.was_too_low {
+                    events.insert(Event::FellBelowLow);
+                }
+            }
+            ComparisonMode::TransparentHysteresis => {
+                if status.was_too_high && !previous.was_too_high {
+                    events.insert(Event::RoseAboveHigh);
+                }
+                if (!status.was_too_high && previous.was_too_high)
+                    || (status.was_too_low && !previous.was_too_low)
+                {
+                    events.insert(Event::FellBelowLow);
+                }
+            }
+        }
+        if status.conversion_ready {
+            events.insert(Event::ConversionReady);
+        }
+        if status.has_overflown {
+            events.insert(Event::Overflow);
+        }
+        self.state.pending_events.insert_all(events);
+        self.state.last_status = status;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_iterate_events() {
+        let mut events = Events::from(Event::Overflow);
+        events.insert(Event::RoseAboveHigh);
+        let mut iter = events.iter();
+        assert_eq!(Some(Event::RoseAboveHigh), iter.next());
+        assert_eq!(Some(Event::Overflow), iter.next());
+        assert_eq!(None, iter.next());
+    }
+
+    #[test]
+    fn can_remove_events() {
+        let mut events = Events::from(Event::FellBelowLow);
+        assert!(events.contains(Event::FellBelowLow));
+        events.remove(Event::FellBelowLow);
+        assert!(events.is_empty());
+    }
+}
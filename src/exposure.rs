@@ -0,0 +1,287 @@
+//! Light exposure integration
+use crate::{CompletedDays, Exposure, ExposureIntegrator, LightSource, Measurement};
+
+/// Microseconds in an hour
+const US_PER_HOUR: f64 = 3_600_000_000.0;
+/// Microseconds in a day
+const US_PER_DAY: u64 = 86_400_000_000;
+
+impl LightSource {
+    /// Conversion factor from lux to photosynthetic photon flux density
+    /// (PPFD) in µmol/m²/s.
+    ///
+    /// The factors of the predefined light sources are typical values.
+    /// For accurate results, calibrate a custom factor with a quantum sensor.
+    pub fn ppfd_per_lux(self) -> f32 {
+        match self {
+            LightSource::Sunlight => 0.0185,
+            LightSource::CoolWhiteFluorescent => 0.0135,
+            LightSource::HighPressureSodium => 0.0122,
+            LightSource::MetalHalide => 0.0141,
+            LightSource::Custom(factor) => factor,
+        }
+    }
+}
+
+impl ExposureIntegrator {
+    /// Create a new instance.
+    ///
+    /// The days start at `day_start_us` and every 24 hours after it in the
+    /// time base of the measurement timestamps. e.g. the timestamp of the
+    /// last local midnight.
+    /// Intervals between measurements longer than `max_gap_us` microseconds
+    /// are not integrated but reported as gaps.
+    pub fn new(source: LightSource, day_start_us: u64, max_gap_us: u64) -> Self {
+        ExposureIntegrator {
+            source,
+            day_start_us,
+            max_gap_us,
+            lux_us: 0.0,
+            gap_us: 0,
+            overflows: 0,
+            last: None,
+        }
+    }
+
+    /// Add a measurement in lux with its timestamp in microseconds.
+    ///
+    /// The illuminance is integrated linearly between consecutive measurements.
+    /// Timestamps must be monotonic. Overflown measurements are integrated
+    /// with their reported value, so the exposure is then only a lower bound.
+    ///
+    /// When day boundaries are crossed, the accumulated exposure is reset and
+    /// the exposures of the completed days are returned. The days entirely
+    /// between two measurements are integrated the same way, or reported
+    /// as a gap if the measurements are too far apart.
+    pub fn add(&mut self, lux: f32, has_overflown: bool, timestamp: u64) -> CompletedDays {
+        let mut completed = CompletedDays::none(self.source);
+        if let Some(last) = self.last {
+            let current = (timestamp, lux);
+            let is_gap = timestamp.saturating_sub(last.0) > self.max_gap_us;
+            let last_day = self.day(last.0);
+            let day = self.day(timestamp);
+            let mut from = last;
+            if day > last_day {
+                let day_end = self.day_start(last_day + 1);
+                self.integrate(from, (day_end, interpolate(last, current, day_end)), is_gap);
+                let day_start = self.day_start(day);
+                completed = CompletedDays {
+                    first: Some(self.exposure()),
+                    source: self.source,
+                    from: last,
+                    to: current,
+                    is_gap,
+                    next_start: day_end,
+                    remaining: (day - last_day - 1) as u64,
+                };
+                self.reset();
+                from = (day_start, interpolate(last, current, day_start));
+            }
+            self.integrate(from, current, is_gap);
+        }
+        if has_overflown {
+            self.overflows = self.overflows.saturating_add(1);
+        }
+        self.last = Some((timestamp, lux));
+        completed
+    }
+
+    /// Add a timestamped measurement.
+    ///
+    /// Measurements without timestamp are ignored.
+    /// See [`add()`](ExposureIntegrator::add) and
+    /// [`read_measurement_timestamped()`](crate::Opt300x::read_measurement_timestamped).
+    pub fn add_measurement(&mut self, measurement: &Measurement<f32>) -> CompletedDays {
+        match measurement.timestamp {
+            Some(timestamp) => self.add(
+                measurement.result,
+                measurement.status.has_overflown,
+                timestamp,
+            ),
+            None => CompletedDays::none(self.source),
+        }
+    }
+
+    /// Exposure accumulated in the current day.
+    pub fn exposure(&self) -> Exposure {
+        exposure(self.source, self.lux_us, self.gap_us, self.overflows)
+    }
+
+    /// Reset the exposure accumulated in the current day.
+    ///
+    /// The last measurement is kept so that the integration continues
+    /// from it.
+    pub fn reset(&mut self) {
+        self.lux_us = 0.0;
+        self.gap_us = 0;
+        self.overflows = 0;
+    }
+
+    /// Index of the day containing a timestamp. Negative before `day_start_us`.
+    fn day(&self, timestamp: u64) -> i128 {
+        (i128::from(timestamp) - i128::from(self.day_start_us)).div_euclid(i128::from(US_PER_DAY))
+    }
+
+    /// Timestamp of the start of a day.
+    ///
+    /// Only valid for days starting at or after the first measurement.
+    fn day_start(&self, day: i128) -> u64 {
+        (i128::from(self.day_start_us) + day * i128::from(US_PER_DAY)) as u64
+    }
+
+    fn integrate(&mut self, from: (u64, f32), to: (u64, f32), is_gap: bool) {
+        let duration = to.0.saturating_sub(from.0);
+        if is_gap {
+            self.gap_us = self.gap_us.saturating_add(duration);
+        } else {
+            self.lux_us += integral(from, to);
+        }
+    }
+}
+
+impl CompletedDays {
+    fn none(source: LightSource) -> Self {
+        CompletedDays {
+            first: None,
+            source,
+            from: (0, 0.0),
+            to: (0, 0.0),
+            is_gap: false,
+            next_start: 0,
+            remaining: 0,
+        }
+    }
+}
+
+impl Iterator for CompletedDays {
+    type Item = Exposure;
+
+    fn next(&mut self) -> Option<Exposure> {
+        if let Some(first) = self.first.take() {
+            return Some(first);
+        }
+        if self.remaining == 0 {
+            return None;
+        }
+        let start = self.next_start;
+        let end = start + US_PER_DAY;
+        self.next_start = end;
+        self.remaining -= 1;
+        Some(if self.is_gap {
+            exposure(self.source, 0.0, US_PER_DAY, 0)
+        } else {
+            let from = (start, interpolate(self.from, self.to, start));
+            let to = (end, interpolate(self.from, self.to, end));
+            exposure(self.source, integral(from, to), 0, 0)
+        })
+    }
+}
+
+fn exposure(source: LightSource, lux_us: f64, gap_us: u64, overflows: u32) -> Exposure {
+    let lux_hours = lux_us / US_PER_HOUR;
+    // µmol/m²/s * µs = 1e-12 mol/m²
+    let light_integral = lux_us * f64::from(source.ppfd_per_lux()) * 1e-12;
+    Exposure {
+        lux_hours: lux_hours as f32,
+        light_integral: light_integral as f32,
+        gap_us,
+        overflows,
+    }
+}
+
+/// Illuminance integrated linearly between two measurements in lux-µs
+fn integral(from: (u64, f32), to: (u64, f32)) -> f64 {
+    let duration = to.0.saturating_sub(from.0);
+    duration as f64 * f64::from(from.1 + to.1) / 2.0
+}
+
+/// Illuminance at a timestamp between two measurements
+fn interpolate(from: (u64, f32), to: (u64, f32), at: u64) -> f32 {
+    let fraction = (at - from.0) as f32 / (to.0 - from.0) as f32;
+    from.1 + (to.1 - from.1) * fraction
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: u64 = 3_600_000_000;
+    const DAY: u64 = 24 * HOUR;
+
+    fn assert_near(expected: f32, value: f32) {
+        assert!((expected - value).abs() < 1e-3, "{} != {}", expected, value);
+    }
+
+    #[test]
+    fn integrates_lux_hours() {
+        let mut integrator = ExposureIntegrator::new(LightSource::Sunlight, 0, HOUR);
+        assert_eq!(None, integrator.add(100.0, false, 0).next());
+        assert_eq!(None, integrator.add(300.0, false, HOUR).next());
+        assert_eq!(None, integrator.add(300.0, false, 2 * HOUR).next());
+        let exposure = integrator.exposure();
+        assert_near(500.0, exposure.lux_hours);
+        // 500 lux-hours * 3600 s/h * 0.0185 µmol/m²/s/lux
+        assert_near(0.0333, exposure.light_integral);
+        assert_eq!(0, exposure.gap_us);
+    }
+
+    #[test]
+    fn gaps_are_not_integrated() {
+        let mut integrator = ExposureIntegrator::new(LightSource::Custom(0.02), 0, HOUR);
+        integrator.add(100.0, false, 0);
+        integrator.add(100.0, true, 3 * HOUR);
+        let exposure = integrator.exposure();
+        assert_eq!(0.0, exposure.lux_hours);
+        assert_eq!(3 * HOUR, exposure.gap_us);
+        assert_eq!(1, exposure.overflows);
+    }
+
+    #[test]
+    fn resets_at_day_boundary() {
+        let day_start = 6 * HOUR;
+        let mut integrator = ExposureIntegrator::new(LightSource::Sunlight, day_start, 3 * HOUR);
+        integrator.add(100.0, false, day_start + 23 * HOUR);
+        let mut completed = integrator.add(300.0, false, day_start + 25 * HOUR);
+        assert_near(150.0, completed.next().unwrap().lux_hours);
+        assert_eq!(None, completed.next());
+        let exposure = integrator.exposure();
+        assert_near(250.0, exposure.lux_hours);
+        assert_eq!(0, exposure.gap_us);
+    }
+
+    #[test]
+    fn timestamps_before_day_start_belong_to_previous_day() {
+        let mut integrator = ExposureIntegrator::new(LightSource::Sunlight, 6 * HOUR, 6 * HOUR);
+        assert_eq!(None, integrator.add(100.0, false, 0).next());
+        assert_eq!(None, integrator.add(100.0, false, HOUR).next());
+        let mut completed = integrator.add(100.0, false, 7 * HOUR);
+        assert_near(600.0, completed.next().unwrap().lux_hours);
+        assert_eq!(None, completed.next());
+        assert_near(100.0, integrator.exposure().lux_hours);
+    }
+
+    #[test]
+    fn reports_days_between_measurements() {
+        let mut integrator = ExposureIntegrator::new(LightSource::Sunlight, 0, 3 * DAY);
+        integrator.add(100.0, false, 12 * HOUR);
+        let mut completed = integrator.add(100.0, false, 2 * DAY + HOUR);
+        assert_near(1200.0, completed.next().unwrap().lux_hours);
+        assert_near(2400.0, completed.next().unwrap().lux_hours);
+        assert_eq!(None, completed.next());
+        assert_near(100.0, integrator.exposure().lux_hours);
+    }
+
+    #[test]
+    fn reports_days_between_measurements_as_gaps() {
+        let mut integrator = ExposureIntegrator::new(LightSource::Sunlight, 0, HOUR);
+        integrator.add(100.0, false, 12 * HOUR);
+        let mut completed = integrator.add(100.0, false, 3 * DAY + HOUR);
+        assert_eq!(12 * HOUR, completed.next().unwrap().gap_us);
+        assert_eq!(DAY, completed.next().unwrap().gap_us);
+        let day = completed.next().unwrap();
+        assert_eq!(DAY, day.gap_us);
+        assert_eq!(0.0, day.lux_hours);
+        assert_eq!(None, completed.next());
+        assert_eq!(HOUR, integrator.exposure().gap_us);
+    }
+}
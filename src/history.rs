@@ -0,0 +1,340 @@
+//! Measurement history and statistics
+use crate::{measurement::lsb, Aggregate, AggregateBuffer, Histogram, History, Measurement};
+
+/// Decades of lux covered by the histogram, starting at the lowest LSB
+const HISTOGRAM_DECADES: f32 = 7.0;
+/// Microseconds in a second
+const US_PER_SECOND: u64 = 1_000_000;
+
+impl Aggregate {
+    fn new(start: u64) -> Self {
+        Aggregate {
+            start,
+            count: 0,
+            overflows: 0,
+            min: f32::INFINITY,
+            max: f32::NEG_INFINITY,
+            sum: 0.0,
+        }
+    }
+
+    fn add(&mut self, lux: f32, has_overflown: bool) {
+        if has_overflown {
+            self.overflows = self.overflows.saturating_add(1);
+        } else {
+            self.count = self.count.saturating_add(1);
+            self.min = self.min.min(lux);
+            self.max = self.max.max(lux);
+            self.sum += f64::from(lux);
+        }
+    }
+
+    /// Lowest valid measurement in lux.
+    pub fn min(&self) -> Option<f32> {
+        self.stat(self.min)
+    }
+
+    /// Highest valid measurement in lux.
+    pub fn max(&self) -> Option<f32> {
+        self.stat(self.max)
+    }
+
+    /// Mean of the valid measurements in lux.
+    pub fn mean(&self) -> Option<f32> {
+        self.stat((self.sum / f64::from(self.count)) as f32)
+    }
+
+    fn stat(&self, value: f32) -> Option<f32> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(value)
+        }
+    }
+}
+
+impl<const N: usize> AggregateBuffer<N> {
+    /// Create a new empty instance with time slots of `period_us` microseconds.
+    ///
+    /// The time slots are aligned to multiples of the period.
+    pub fn new(period_us: u64) -> Self {
+        AggregateBuffer {
+            period_us: period_us.max(1),
+            aggregates: [Aggregate::new(0); N],
+            next: 0,
+            len: 0,
+            current: None,
+        }
+    }
+
+    /// Add a measurement in lux with its timestamp in microseconds.
+    ///
+    /// Timestamps must be monotonic. The aggregate of the current time slot
+    /// is completed and stored when a measurement of a later slot is added.
+    /// Slots without measurements are skipped. Once the buffer is full, the
+    /// oldest aggregate is discarded.
+    pub fn add(&mut self, lux: f32, has_overflown: bool, timestamp: u64) {
+        let start = timestamp - timestamp % self.period_us;
+        let mut current = match self.current {
+            Some(current) if current.start >= start => current,
+            Some(current) => {
+                self.push(current);
+                Aggregate::new(start)
+            }
+            None => Aggregate::new(start),
+        };
+        current.add(lux, has_overflown);
+        self.current = Some(current);
+    }
+
+    /// Aggregate of the current, incomplete, time slot.
+    pub fn current(&self) -> Option<&Aggregate> {
+        self.current.as_ref()
+    }
+
+    /// Completed aggregate at `index`, where 0 is the most recent one.
+    pub fn get(&self, index: usize) -> Option<&Aggregate> {
+        if index >= self.len {
+            return None;
+        }
+        Some(&self.aggregates[(self.next + N - 1 - index) % N])
+    }
+
+    /// Iterate over the completed aggregates from the most recent to the oldest.
+    pub fn iter(&self) -> impl Iterator<Item = &Aggregate> {
+        (0..self.len).filter_map(move |index| self.get(index))
+    }
+
+    /// Number of completed aggregates.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether there are no completed aggregates.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Discard all aggregates.
+    pub fn clear(&mut self) {
+        self.next = 0;
+        self.len = 0;
+        self.current = None;
+    }
+
+    fn push(&mut self, aggregate: Aggregate) {
+        if N == 0 {
+            return;
+        }
+        self.aggregates[self.next] = aggregate;
+        self.next = (self.next + 1) % N;
+        self.len = (self.len + 1).min(N);
+    }
+}
+
+impl<const N: usize> Default for Histogram<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Histogram<N> {
+    /// Create a new empty histogram.
+    ///
+    /// The bins are logarithmically spaced between 0.01 lux and 100000 lux.
+    pub fn new() -> Self {
+        Histogram { counts: [0; N] }
+    }
+
+    /// Add a measurement in lux.
+    ///
+    /// Values outside the histogram range are counted in the first or last bin.
+    pub fn add(&mut self, lux: f32) {
+        if let Some(count) = self.counts.get_mut(Self::bin(lux)) {
+            *count = count.saturating_add(1);
+        }
+    }
+
+    /// Number of measurements in each bin.
+    pub fn counts(&self) -> &[u32; N] {
+        &self.counts
+    }
+
+    /// Index of the bin for a value in lux.
+    pub fn bin(lux: f32) -> usize {
+        let decades = libm::log10f(lux / lsb(0));
+        if decades.is_nan() || decades <= 0.0 {
+            return 0;
+        }
+        ((decades * N as f32 / HISTOGRAM_DECADES) as usize).min(N.saturating_sub(1))
+    }
+
+    /// Lower and upper limits of a bin in lux.
+    pub fn bin_range(index: usize) -> (f32, f32) {
+        let limit =
+            |index: usize| lsb(0) * libm::powf(10.0, HISTOGRAM_DECADES * index as f32 / N as f32);
+        (limit(index), limit(index + 1))
+    }
+
+    /// Reset all counts.
+    pub fn clear(&mut self) {
+        self.counts = [0; N];
+    }
+}
+
+impl<const S: usize, const M: usize, const H: usize, const B: usize> Default
+    for History<S, M, H, B>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const S: usize, const M: usize, const H: usize, const B: usize> History<S, M, H, B> {
+    /// Create a new empty history.
+    pub fn new() -> Self {
+        History {
+            seconds: AggregateBuffer::new(US_PER_SECOND),
+            minutes: AggregateBuffer::new(60 * US_PER_SECOND),
+            hours: AggregateBuffer::new(3600 * US_PER_SECOND),
+            histogram: Histogram::new(),
+        }
+    }
+
+    /// Add a timestamped measurement.
+    ///
+    /// Measurements without timestamp are ignored and `false` is returned.
+    /// Measurements with an overflow condition are only counted as such.
+    /// See [`read_measurement_timestamped()`](crate::Opt300x::read_measurement_timestamped).
+    pub fn add(&mut self, measurement: &Measurement<f32>) -> bool {
+        let timestamp = match measurement.timestamp {
+            Some(timestamp) => timestamp,
+            None => return false,
+        };
+        let (lux, has_overflown) = (measurement.result, measurement.status.has_overflown);
+        self.seconds.add(lux, has_overflown, timestamp);
+        self.minutes.add(lux, has_overflown, timestamp);
+        self.hours.add(lux, has_overflown, timestamp);
+        if !has_overflown {
+            self.histogram.add(lux);
+        }
+        true
+    }
+
+    /// Per-second aggregates.
+    pub fn seconds(&self) -> &AggregateBuffer<S> {
+        &self.seconds
+    }
+
+    /// Per-minute aggregates.
+    pub fn minutes(&self) -> &AggregateBuffer<M> {
+        &self.minutes
+    }
+
+    /// Per-hour aggregates.
+    pub fn hours(&self) -> &AggregateBuffer<H> {
+        &self.hours
+    }
+
+    /// Histogram of the valid measurements.
+    pub fn histogram(&self) -> &Histogram<B> {
+        &self.histogram
+    }
+
+    /// Discard all aggregates and reset the histogram.
+    pub fn clear(&mut self) {
+        self.seconds.clear();
+        self.minutes.clear();
+        self.hours.clear();
+        self.histogram.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IntegrationTime, MeasurementInfo, Status};
+
+    fn measurement(result: f32, has_overflown: bool, timestamp: Option<u64>) -> Measurement<f32> {
+        Measurement {
+            result,
+            status: Status {
+                has_overflown,
+                ..Status::default()
+            },
+            info: MeasurementInfo {
+                exponent: 0,
+                integration_time: IntegrationTime::Ms800,
+            },
+            timestamp,
+            stale: false,
+        }
+    }
+
+    #[test]
+    fn can_aggregate_time_slots() {
+        let mut buffer = AggregateBuffer::<2>::new(1_000);
+        buffer.add(10.0, false, 100);
+        buffer.add(30.0, false, 900);
+        buffer.add(5.0, true, 950);
+        assert!(buffer.is_empty());
+        buffer.add(50.0, false, 5_000);
+        assert_eq!(1, buffer.len());
+        let aggregate = buffer.get(0).unwrap();
+        assert_eq!(0, aggregate.start);
+        assert_eq!(2, aggregate.count);
+        assert_eq!(1, aggregate.overflows);
+        assert_eq!(Some(10.0), aggregate.min());
+        assert_eq!(Some(30.0), aggregate.max());
+        assert_eq!(Some(20.0), aggregate.mean());
+        assert_eq!(5_000, buffer.current().unwrap().start);
+    }
+
+    #[test]
+    fn buffer_keeps_most_recent_aggregates() {
+        let mut buffer = AggregateBuffer::<2>::new(1_000);
+        for i in 0..4 {
+            buffer.add(i as f32, false, i * 1_000);
+        }
+        assert_eq!(2, buffer.len());
+        let starts: [u64; 2] = [buffer.get(0).unwrap().start, buffer.get(1).unwrap().start];
+        assert_eq!([2_000, 1_000], starts);
+        assert_eq!(2, buffer.iter().count());
+        assert_eq!(None, buffer.get(2));
+    }
+
+    #[test]
+    fn aggregate_with_only_overflows_has_no_statistics() {
+        let mut aggregate = Aggregate::new(0);
+        aggregate.add(83_865.6, true);
+        assert_eq!(None, aggregate.min());
+        assert_eq!(None, aggregate.mean());
+    }
+
+    #[test]
+    fn histogram_bins_are_logarithmic() {
+        assert_eq!(0, Histogram::<7>::bin(0.0));
+        assert_eq!(0, Histogram::<7>::bin(0.05));
+        assert_eq!(1, Histogram::<7>::bin(0.5));
+        assert_eq!(4, Histogram::<7>::bin(500.0));
+        assert_eq!(6, Histogram::<7>::bin(83_865.6));
+        let (low, high) = Histogram::<7>::bin_range(4);
+        assert!((low - 100.0).abs() < 0.01);
+        assert!((high - 1000.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn history_ingests_measurements() {
+        let mut history = History::<4, 4, 4, 7>::new();
+        assert!(!history.add(&measurement(100.0, false, None)));
+        assert!(history.add(&measurement(100.0, false, Some(0))));
+        assert!(history.add(&measurement(200.0, true, Some(800_000))));
+        assert!(history.add(&measurement(300.0, false, Some(1_600_000))));
+        assert_eq!(1, history.seconds().len());
+        assert!(history.minutes().is_empty());
+        let minute = history.minutes().current().unwrap();
+        assert_eq!(2, minute.count);
+        assert_eq!(1, minute.overflows);
+        assert_eq!(2, history.histogram().counts()[4]);
+    }
+}
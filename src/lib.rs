@@ -5,26 +5,52 @@
 //!
 //! This driver allows you to:
 //! - Read the measurement in lux or raw. See: [`read_lux()`].
+//! - Read the measurement range, resolution and uncertainty. See: [`MeasurementInfo`].
+//! - Trigger and poll one-shot conversions explicitly. See: [`start_conversion()`].
 //! - Change into continuous measurement mode. See: [`into_continuous()`].
+//! - Switch the measurement mode at runtime without consuming the driver. See: [`into_runtime_mode()`].
 //! - Read the conversion status. See: [`read_status()`].
+//! - Read and acknowledge threshold events. See: [`read_events()`].
 //! - Set the fault count. See: [`set_fault_count()`].
 //! - Set the interrupt pin polarity. See: [`set_interrupt_pin_polarity()`].
 //! - Set the comparison mode. See: [`set_comparison_mode()`].
 //! - Set the low and high limits. See: [`set_low_limit_raw()`].
+//! - Set a threshold window or hysteresis in lux. See: [`set_window()`].
+//! - Wake up on illuminance changes with a self-recentering window. See: [`WakeOnChange`].
+//! - Track several light level zones with hysteresis. See: [`ZoneTracker`].
+//! - Debounce the threshold alarms in software. See: [`Debouncer`].
+//! - Detect lights being switched on and off. See: [`StepDetector`].
+//! - Estimate the illuminance trend. See: [`TrendEstimator`].
+//! - Keep a multi-resolution history and histogram of the measurements. See: [`History`].
+//! - Integrate the light exposure and daily light integral. See: [`ExposureIntegrator`].
+//! - Oversample low light levels to a target precision. See: [`Oversampler`].
+//! - Adapt the integration time to the light conditions. See: [`AdaptiveIntegration`].
+//! - Sample periodically keeping the device shut down in between. See: [`PeriodicSampler`].
+//! - Power the device up and down with a supply-enable pin. See: [`PoweredDown`].
+//! - Decompose the driver and rebuild it later on with its cached state. See: [`into_parts()`].
+//! - Save and restore the device configuration as a compact binary blob. See: [`snapshot()`].
 //! - Enable and disable end-of-conversion mode. See: [`enable_end_of_conversion_mode()`].
 //! - Get the manufacturer ID. See: [`get_manufacturer_id()`].
 //! - Get the device ID. See: [`get_device_id()`].
+//! - Estimate the conversion time and supply current. See: [`power_estimate()`].
 //!
 //! [`read_lux()`]: struct.Opt300x.html#method.read_lux
+//! [`start_conversion()`]: struct.Opt300x.html#method.start_conversion
 //! [`into_continuous()`]: struct.Opt300x.html#method.into_continuous
+//! [`into_runtime_mode()`]: struct.Opt300x.html#method.into_runtime_mode
+//! [`into_parts()`]: struct.Opt300x.html#method.into_parts
+//! [`snapshot()`]: struct.Opt300x.html#method.snapshot
 //! [`read_status()`]: struct.Opt300x.html#method.read_status
+//! [`read_events()`]: struct.Opt300x.html#method.read_events
 //! [`set_fault_count()`]: struct.Opt300x.html#method.set_fault_count
 //! [`set_interrupt_pin_polarity()`]: struct.Opt300x.html#method.set_interrupt_pin_polarity
 //! [`set_comparison_mode()`]: struct.Opt300x.html#method.set_comparison_mode
 //! [`set_low_limit_raw()`]: struct.Opt300x.html#method.set_low_limit_raw
+//! [`set_window()`]: struct.Opt300x.html#method.set_window
 //! [`enable_end_of_conversion_mode()`]: struct.Opt300x.html#method.enable_end_of_conversion_mode
 //! [`get_manufacturer_id()`]: struct.Opt300x.html#method.get_manufacturer_id
 //! [`get_device_id()`]: struct.Opt300x.html#method.get_device_id
+//! [`power_estimate()`]: struct.Opt300x.html#method.power_estimate
 //!
 //! [Introductory blog post](https://blog.eldruin.com/opt300x-ambient-light-sensor-driver-in-rust/)
 //!
@@ -136,6 +162,22 @@
 //! }
 //! ```
 //!
+//! ### Switch the measurement mode at runtime
+//!
+//! ```no_run
+//! use linux_embedded_hal::I2cdev;
+//! use opt300x::{MeasurementMode, Opt300x, SlaveAddr};
+//!
+//! let dev = I2cdev::new("/dev/i2c-1").unwrap();
+//! let address = SlaveAddr::default();
+//! let mut sensor = Opt300x::new_opt3001(dev, address).into_runtime_mode();
+//! let measurement = nb::block!(sensor.read_measurement()).unwrap();
+//! println!("one-shot lux: {:2}", measurement.result);
+//! sensor.set_mode(MeasurementMode::Continuous).unwrap();
+//! let measurement = nb::block!(sensor.read_measurement()).unwrap();
+//! println!("continuous lux: {:2}", measurement.result);
+//! ```
+//!
 //! ### Set the integration time and manual lux range
 //!
 //! ```no_run
@@ -187,6 +229,26 @@
 //!     println!("status {:?}", status);
 //! }
 //! ```
+//!
+//! ### Wake up on illuminance changes
+//!
+//! ```no_run
+//! use linux_embedded_hal::I2cdev;
+//! use opt300x::{FaultCount, Opt300x, SlaveAddr, WakeOnChange};
+//!
+//! let dev = I2cdev::new("/dev/i2c-1").unwrap();
+//! let address = SlaveAddr::default();
+//! let sensor = Opt300x::new_opt3001(dev, address);
+//! let mut sensor = sensor.into_continuous().ok().unwrap();
+//! let mut wake = WakeOnChange::new(10.0, FaultCount::Two);
+//! nb::block!(wake.start(&mut sensor)).unwrap();
+//! loop {
+//!     // Sleep until the interrupt pin is asserted
+//!     if let Some(change) = wake.handle_interrupt(&mut sensor).unwrap() {
+//!         println!("{} lux -> {} lux", change.previous, change.current);
+//!     }
+//! }
+//! ```
 
 #![deny(unsafe_code, missing_docs)]
 #![no_std]
@@ -213,6 +275,46 @@ pub enum ModeChangeError<E, DEV> {
     I2C(E, DEV),
 }
 
+/// Error type for the conversion period calibration.
+///
+/// See [`Opt300x::calibrate_conversion_period()`].
+#[derive(Debug)]
+pub enum CalibrationError<E> {
+    /// I²C bus communication error
+    I2C(E),
+    /// Invalid input data provided
+    InvalidInputData,
+    /// A conversion was not ready within the expected time.
+    Timeout,
+}
+
+/// Error type for configuration restoring.
+///
+/// See [`Opt300x::restore()`].
+#[derive(Debug)]
+pub enum RestoreError<E> {
+    /// I²C bus communication error
+    I2C(E),
+    /// The registers read back from the device differ from the snapshot.
+    VerificationFailed,
+}
+
+/// Error type for power state changes.
+///
+/// This allows to retrieve the unchanged device in case of an error.
+pub enum PowerError<E, PE, DEV> {
+    /// I²C bus error while restoring the configuration after powering up.
+    ///
+    /// `E` is the error that happened.
+    /// `DEV` is the device, which was powered down again.
+    I2C(E, DEV),
+    /// Error setting the supply-enable pin.
+    ///
+    /// `PE` is the error that happened.
+    /// `DEV` is the device with the power state unchanged.
+    Pin(PE, DEV),
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 struct Config {
     bits: u16,
@@ -231,6 +333,31 @@ impl Config {
     }
 }
 
+/// Observation times of the conversion in progress in microseconds
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct ConversionTimes {
+    started_at: Option<u64>,
+    not_ready_at: Option<u64>,
+    last_end: Option<u64>,
+}
+
+/// Debounced state of a threshold alarm
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DebounceState {
+    delays: DebounceDelays,
+    asserted: bool,
+    /// Time and number of observations of the opposite condition
+    pending: Option<(u64, u32)>,
+}
+
+/// Light step candidate waiting for the minimum dwell time
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct StepCandidate {
+    before: f32,
+    after: f32,
+    since: u64,
+}
+
 /// IC markers
 #[doc(hidden)]
 pub mod ic {
@@ -251,6 +378,10 @@ pub mod ic {
 pub mod marker {
     use super::private;
     pub trait WithDeviceId: private::Sealed {}
+    pub trait Mode: private::Sealed {
+        /// Whether the driver can be in the given measurement mode
+        fn allows(mode: super::MeasurementMode) -> bool;
+    }
 }
 
 /// Mode marker
@@ -259,16 +390,52 @@ pub mod mode {
     pub struct OneShot(());
     /// Continuous measurement mode
     pub struct Continuous(());
+    /// Measurement mode selected at runtime
+    pub struct Runtime(());
 }
 
 /// OPT300x device driver
 #[derive(Debug)]
 pub struct Opt300x<I2C, IC, MODE> {
     i2c: I2C,
+    state: DriverState<IC, MODE>,
+}
+
+/// Snapshot of the device configuration
+///
+/// This contains the configuration register without the status flags and
+/// the measurement mode as well as both limit registers, which include
+/// the end-of-conversion mode.
+/// See [`Opt300x::snapshot()`] and [`ConfigSnapshot::to_bytes()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConfigSnapshot {
+    config: u16,
+    low_limit: u16,
+    high_limit: u16,
+}
+
+/// Cached state of the driver
+///
+/// This allows to rebuild the driver later on without accessing the device.
+/// See [`Opt300x::into_parts()`] and [`DriverState::to_bytes()`].
+pub struct DriverState<IC, MODE> {
     address: u8,
     config: Config,
     low_limit: u16,
+    high_limit: u16,
+    end_of_conversion_mode: bool,
     was_conversion_started: bool,
+    conversion_period_scale: Option<f32>,
+    conversion_times: ConversionTimes,
+    config_generation: u16,
+    conversion_generation: u16,
+    conversion_id: u16,
+    in_flight_config_policy: InFlightConfigPolicy,
+    pending_config: Option<Config>,
+    last_config_change: Option<ConfigChange>,
+    pending_events: Events,
+    last_status: Status,
+    conversion_ready_pending: bool,
     _ic: PhantomData<IC>,
     _mode: PhantomData<MODE>,
 }
@@ -307,6 +474,11 @@ pub enum InterruptPinPolarity {
 }
 
 /// Lux range
+///
+/// The manual ranges are available as constants named after their full-scale
+/// value in lux. e.g. `LuxRange::LUX_40_95` is `LuxRange::Manual(0)`.
+/// See also [`LuxRange::full_scale()`], [`LuxRange::lsb()`] and
+/// [`LuxRange::plan()`].
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LuxRange {
     /// Manual [0-11]
@@ -315,6 +487,17 @@ pub enum LuxRange {
     Auto,
 }
 
+/// Recommended configuration for a manual lux range.
+///
+/// See [`LuxRange::plan()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RangePlan {
+    /// Lux range to use
+    pub lux_range: LuxRange,
+    /// Integration time to use
+    pub integration_time: IntegrationTime,
+}
+
 /// Integration time
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum IntegrationTime {
@@ -324,6 +507,70 @@ pub enum IntegrationTime {
     Ms800,
 }
 
+/// Measurement mode
+///
+/// This is the runtime counterpart of the [`mode`] markers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeasurementMode {
+    /// One-shot measurements. The device is shut down between conversions.
+    OneShot,
+    /// Continuous measurements.
+    Continuous,
+}
+
+/// Conversion timing and supply current estimate for a configuration.
+///
+/// See [`PowerEstimate::new()`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PowerEstimate {
+    /// Expected conversion time in microseconds.
+    ///
+    /// This includes the automatic full-scale range selection time, if used.
+    pub conversion_time_us: u32,
+    /// Maximum conversion time in microseconds.
+    pub max_conversion_time_us: u32,
+    /// Estimated average supply current in microamperes.
+    pub average_current_ua: f32,
+}
+
+/// Behavior when changing the configuration while a one-shot conversion
+/// is in progress
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InFlightConfigPolicy {
+    /// Restart the conversion with the new configuration (default)
+    ///
+    /// The time of the restart is not known to the driver, so the completion
+    /// time of the restarted conversion is only estimated from the times at
+    /// which its conversion ready flag was observed.
+    Restart,
+    /// Apply the new configuration once the conversion is complete
+    Defer,
+}
+
+/// What happened during a configuration change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigChange {
+    /// The configuration was applied immediately.
+    Applied,
+    /// The conversion in progress was restarted with the new configuration.
+    ConversionRestarted,
+    /// The configuration will be applied once the conversion in progress
+    /// is complete.
+    Deferred,
+}
+
+/// Monotonic clock
+///
+/// Used to time conversions. This is implemented for closures returning
+/// the current time so that any timer can be used, for example:
+/// `let mut clock = || timer.now().ticks() / 1000;`
+pub trait Clock {
+    /// Get the current time in microseconds.
+    ///
+    /// The value must never decrease.
+    fn now_us(&mut self) -> u64;
+}
+
 /// Result comparison mode for interrupt reporting
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ComparisonMode {
@@ -346,6 +593,401 @@ pub struct Status {
     pub was_too_low: bool,
 }
 
+/// Threshold, conversion and overflow event
+///
+/// Threshold events are interpreted according to the current
+/// [`ComparisonMode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// The result rose above the high limit.
+    RoseAboveHigh,
+    /// The result fell below the low limit.
+    FellBelowLow,
+    /// A new conversion is ready.
+    ConversionReady,
+    /// An overflow condition occurred during a conversion.
+    Overflow,
+}
+
+/// Set of events
+///
+/// See [`Opt300x::read_events()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Events {
+    bits: u8,
+}
+
+/// Self-recentering threshold window to wake up on illuminance changes
+///
+/// See [`WakeOnChange::start()`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WakeOnChange {
+    threshold_percent: f32,
+    fault_count: FaultCount,
+    center: Option<f32>,
+}
+
+/// Illuminance change detected by [`WakeOnChange`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LuxChange {
+    /// Event which triggered the change: `RoseAboveHigh` or `FellBelowLow`
+    pub event: Event,
+    /// Previous window center in lux
+    pub previous: f32,
+    /// Current illuminance in lux. This is the new window center.
+    pub current: f32,
+}
+
+/// Boundary between two light level zones
+///
+/// See [`ZoneTracker`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ZoneBoundary {
+    /// Illuminance in lux which must be exceeded to enter the upper zone
+    pub lux: f32,
+    /// Hysteresis in lux. The result must fall below `lux - hysteresis`
+    /// to return to the lower zone.
+    pub hysteresis: f32,
+}
+
+/// Light level zone tracker
+///
+/// See [`ZoneTracker::start()`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ZoneTracker<'a> {
+    boundaries: &'a [ZoneBoundary],
+    fault_count: FaultCount,
+    zone: Option<usize>,
+}
+
+/// Transition between light level zones detected by [`ZoneTracker`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ZoneTransition {
+    /// Previous zone
+    pub from: usize,
+    /// Current zone
+    pub to: usize,
+    /// Current illuminance in lux
+    pub lux: f32,
+}
+
+/// Persistence required before changing the state of an alarm
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Persistence {
+    /// Number of consecutive conversions
+    Conversions(u32),
+    /// Time in microseconds
+    Microseconds(u64),
+}
+
+/// Assert and deassert delays of a threshold alarm
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebounceDelays {
+    /// Persistence of the threshold condition required to assert the alarm
+    pub assert: Persistence,
+    /// Persistence of the normal condition required to deassert the alarm
+    pub deassert: Persistence,
+}
+
+/// Debounced threshold alarms
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AlarmState {
+    /// Whether the high limit alarm is asserted
+    pub high: bool,
+    /// Whether the low limit alarm is asserted
+    pub low: bool,
+}
+
+/// Software debouncing of the threshold flags
+///
+/// See [`Debouncer::update()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Debouncer {
+    high: DebounceState,
+    low: DebounceState,
+}
+
+/// Thresholds for a significant illuminance change
+///
+/// A change is significant if it exceeds both thresholds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChangeThresholds {
+    /// Change relative to the higher of both levels. e.g. 0.5 for 50%
+    pub relative: f32,
+    /// Absolute change in lux
+    pub absolute: f32,
+}
+
+/// Kind of illuminance change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// Abrupt increase. e.g. a light was switched on.
+    StepOn,
+    /// Abrupt decrease. e.g. a light was switched off.
+    StepOff,
+    /// Slow change. e.g. daylight drift.
+    Gradual,
+}
+
+/// Illuminance change detected by [`StepDetector`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightChange {
+    /// Kind of change
+    pub kind: ChangeKind,
+    /// Level before the change in lux
+    pub before: f32,
+    /// Level after the change in lux
+    pub after: f32,
+    /// Time of the measurement which completed the detection in microseconds
+    pub timestamp: u64,
+}
+
+/// Step-change detector
+///
+/// See [`StepDetector::update()`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StepDetector {
+    thresholds: ChangeThresholds,
+    min_dwell_us: u64,
+    level: Option<f32>,
+    previous: Option<f32>,
+    candidate: Option<StepCandidate>,
+}
+
+/// Illuminance trend
+///
+/// See [`TrendEstimator::trend()`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Trend {
+    /// Rate of change in decades of lux per minute (`log10(lux)` per minute)
+    pub rate: f32,
+    /// Fitted illuminance at the time of the newest measurement in lux
+    pub lux: f32,
+}
+
+/// Least-squares trend estimator over a sliding window of `N` measurements
+///
+/// See [`TrendEstimator::add()`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrendEstimator<const N: usize> {
+    /// Timestamps in microseconds and `log10(lux)` values
+    samples: [(u64, f32); N],
+    next: usize,
+    len: usize,
+}
+
+/// Statistics of the measurements in a time slot
+///
+/// See [`AggregateBuffer`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aggregate {
+    /// Start of the time slot in microseconds
+    pub start: u64,
+    /// Number of valid measurements
+    pub count: u32,
+    /// Number of measurements with an overflow condition.
+    /// These are not included in the statistics.
+    pub overflows: u32,
+    min: f32,
+    max: f32,
+    sum: f64,
+}
+
+/// Ring buffer of the aggregates of the last `N` time slots
+///
+/// See [`AggregateBuffer::add()`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AggregateBuffer<const N: usize> {
+    period_us: u64,
+    aggregates: [Aggregate; N],
+    next: usize,
+    len: usize,
+    current: Option<Aggregate>,
+}
+
+/// Histogram of light levels with `N` logarithmically spaced bins
+///
+/// See [`Histogram::add()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Histogram<const N: usize> {
+    counts: [u32; N],
+}
+
+/// Multi-resolution measurement history
+///
+/// This contains per-second, per-minute and per-hour aggregates of the
+/// last `S`, `M` and `H` time slots, respectively, as well as a histogram
+/// with `B` bins.
+/// See [`History::add()`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct History<const S: usize, const M: usize, const H: usize, const B: usize> {
+    seconds: AggregateBuffer<S>,
+    minutes: AggregateBuffer<M>,
+    hours: AggregateBuffer<H>,
+    histogram: Histogram<B>,
+}
+
+/// Light source used to convert illuminance into photosynthetic photon flux density
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightSource {
+    /// Sunlight
+    Sunlight,
+    /// Cool white fluorescent lamps
+    CoolWhiteFluorescent,
+    /// High pressure sodium lamps
+    HighPressureSodium,
+    /// Metal halide lamps
+    MetalHalide,
+    /// Custom conversion factor in µmol/m²/s per lux
+    Custom(f32),
+}
+
+/// Accumulated light exposure
+///
+/// See [`ExposureIntegrator`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Exposure {
+    /// Illuminance integrated over time in lux-hours
+    pub lux_hours: f32,
+    /// Light integral in mol/m². For a complete day this is the daily light integral (DLI).
+    pub light_integral: f32,
+    /// Time not covered by the measurements in microseconds
+    pub gap_us: u64,
+    /// Number of measurements with an overflow condition
+    pub overflows: u32,
+}
+
+/// Light exposure integrator
+///
+/// See [`ExposureIntegrator::add()`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExposureIntegrator {
+    source: LightSource,
+    day_start_us: u64,
+    max_gap_us: u64,
+    lux_us: f64,
+    gap_us: u64,
+    overflows: u32,
+    last: Option<(u64, f32)>,
+}
+
+/// Exposures of the days completed by a measurement in chronological order
+///
+/// See [`ExposureIntegrator::add()`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompletedDays {
+    first: Option<Exposure>,
+    source: LightSource,
+    from: (u64, f32),
+    to: (u64, f32),
+    is_gap: bool,
+    next_start: u64,
+    remaining: u64,
+}
+
+/// Averaged result of several conversions
+///
+/// See [`Oversampler`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OversampledMeasurement {
+    /// Mean in lux
+    pub mean: f32,
+    /// Standard deviation of the conversions in lux
+    pub std_dev: f32,
+    /// Standard error of the mean in lux
+    pub std_error: f32,
+    /// Number of conversions
+    pub samples: u32,
+    /// Whether an overflow condition occurred. The light level is then
+    /// too high for the lowest range and the oversampling is stopped.
+    pub has_overflown: bool,
+}
+
+/// Low-light oversampling
+///
+/// See [`Oversampler::start()`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Oversampler {
+    target_precision: f32,
+    max_samples: u32,
+    count: u32,
+    mean: f64,
+    m2: f64,
+}
+
+/// Settings of the adaptive integration time controller
+///
+/// The activity is the smoothed relative change between consecutive conversions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveSettings {
+    /// Activity above which 100 ms conversions are used. e.g. 0.2 for 20%
+    pub fast_activity: f32,
+    /// Activity below which 800 ms conversions may be used
+    pub slow_activity: f32,
+    /// Illuminance in lux below which 800 ms conversions may be used
+    pub dim_lux: f32,
+    /// Illuminance in lux above which 100 ms conversions are used
+    pub bright_lux: f32,
+    /// Minimum number of conversions between integration time changes
+    pub min_conversions: u32,
+    /// Whether to select a manual lux range for 800 ms conversions.
+    /// The automatic full-scale range selection is used otherwise.
+    pub adjust_range: bool,
+}
+
+/// Adaptive integration time controller
+///
+/// See [`AdaptiveIntegration::poll()`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveIntegration {
+    settings: AdaptiveSettings,
+    activity: f32,
+    previous: Option<f32>,
+    conversions: u32,
+}
+
+/// Duty-cycled periodic sampler
+///
+/// See [`PeriodicSampler::sample()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeriodicSampler {
+    period_us: u64,
+    next_deadline: Option<u64>,
+    missed_deadlines: u32,
+}
+
+/// Result of a sampling cycle of [`PeriodicSampler`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    /// Measurement. `None` if the conversion did not complete in time.
+    pub measurement: Option<Measurement<f32>>,
+    /// Time at which the sample was scheduled in microseconds
+    pub deadline: u64,
+    /// Number of deadlines missed since the previous sample
+    pub missed_deadlines: u32,
+}
+
+/// Powered device with its supply-enable pin
+///
+/// This dereferences to the driver so that all its methods are available.
+/// See [`PoweredUp::power_down()`].
+#[derive(Debug)]
+pub struct PoweredUp<I2C, IC, MODE, PIN> {
+    sensor: Opt300x<I2C, IC, MODE>,
+    pin: PIN,
+}
+
+/// Unpowered device with its supply-enable pin
+///
+/// Only the methods which do not communicate with the device are available
+/// through dereferencing.
+/// See [`PoweredDown::power_up()`].
+#[derive(Debug)]
+pub struct PoweredDown<I2C, IC, MODE, PIN> {
+    sensor: Opt300x<I2C, IC, MODE>,
+    pin: PIN,
+}
+
 /// One-shot measurement
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Measurement<T> {
@@ -353,10 +995,61 @@ pub struct Measurement<T> {
     pub result: T,
     /// Conversion status.
     pub status: Status,
+    /// Range, resolution and integration time of the conversion.
+    pub info: MeasurementInfo,
+    /// Estimated time in microseconds at which the conversion was completed.
+    ///
+    /// This is only available when reading with a [`Clock`].
+    pub timestamp: Option<u64>,
+    /// Whether the configuration was changed while the conversion was
+    /// in progress.
+    ///
+    /// In this case the result may reflect the previous configuration.
+    pub stale: bool,
+}
+
+/// Token identifying a one-shot conversion.
+///
+/// See [`Opt300x::start_conversion()`] and [`Opt300x::poll()`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct ConversionToken {
+    id: u16,
+}
+
+/// Information about the conversion a measurement comes from.
+///
+/// This allows to retrieve the full-scale range, the resolution (LSB size)
+/// and an uncertainty bound for a result. See [`MeasurementInfo::full_scale()`],
+/// [`MeasurementInfo::lsb()`] and [`MeasurementInfo::uncertainty()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeasurementInfo {
+    /// Exponent of the result, which selects the effective full-scale range [0-11].
+    pub exponent: u8,
+    /// Integration time used for the conversion.
+    pub integration_time: IntegrationTime,
 }
 
+mod adaptive;
+mod debounce;
 mod device_impl;
+mod events;
+mod exposure;
+mod history;
+mod lux_range;
+mod measurement;
+mod oversampling;
+mod power;
+mod runtime_mode;
+mod sampler;
 mod slave_addr;
+mod snapshot;
+mod state;
+mod step_detection;
+mod thresholds;
+mod timing;
+mod trend;
+mod wake_on_change;
+mod zones;
 
 mod private {
     use super::{ic, mode};
@@ -369,4 +1062,5 @@ mod private {
     impl Sealed for ic::Opt3007 {}
     impl Sealed for mode::OneShot {}
     impl Sealed for mode::Continuous {}
+    impl Sealed for mode::Runtime {}
 }
@@ -0,0 +1,130 @@
+//! Lux range implementation
+use crate::{
+    measurement::{full_scale, lsb, EXPONENT_MAX},
+    IntegrationTime, LuxRange, MeasurementMode, PowerEstimate, RangePlan,
+};
+
+impl LuxRange {
+    /// Manual range with a full-scale value of 40.95 lux
+    pub const LUX_40_95: LuxRange = LuxRange::Manual(0);
+    /// Manual range with a full-scale value of 81.90 lux
+    pub const LUX_81_90: LuxRange = LuxRange::Manual(1);
+    /// Manual range with a full-scale value of 163.80 lux
+    pub const LUX_163_80: LuxRange = LuxRange::Manual(2);
+    /// Manual range with a full-scale value of 327.60 lux
+    pub const LUX_327_60: LuxRange = LuxRange::Manual(3);
+    /// Manual range with a full-scale value of 655.20 lux
+    pub const LUX_655_20: LuxRange = LuxRange::Manual(4);
+    /// Manual range with a full-scale value of 1310.40 lux
+    pub const LUX_1310_40: LuxRange = LuxRange::Manual(5);
+    /// Manual range with a full-scale value of 2620.80 lux
+    pub const LUX_2620_80: LuxRange = LuxRange::Manual(6);
+    /// Manual range with a full-scale value of 5241.60 lux
+    pub const LUX_5241_60: LuxRange = LuxRange::Manual(7);
+    /// Manual range with a full-scale value of 10483.20 lux
+    pub const LUX_10483_20: LuxRange = LuxRange::Manual(8);
+    /// Manual range with a full-scale value of 20966.40 lux
+    pub const LUX_20966_40: LuxRange = LuxRange::Manual(9);
+    /// Manual range with a full-scale value of 41932.80 lux
+    pub const LUX_41932_80: LuxRange = LuxRange::Manual(10);
+    /// Manual range with a full-scale value of 83865.60 lux
+    pub const LUX_83865_60: LuxRange = LuxRange::Manual(11);
+
+    /// Full-scale value of the range in lux.
+    ///
+    /// Returns `None` for `LuxRange::Auto` and invalid manual values.
+    pub fn full_scale(self) -> Option<f32> {
+        self.manual_exponent().map(full_scale)
+    }
+
+    /// Size of the least significant bit (LSB) of the range in lux.
+    ///
+    /// Returns `None` for `LuxRange::Auto` and invalid manual values.
+    pub fn lsb(self) -> Option<f32> {
+        self.manual_exponent().map(lsb)
+    }
+
+    /// Recommend a manual lux range and integration time.
+    ///
+    /// This selects the lowest manual range whose full-scale value covers
+    /// `max_lux` and checks that its LSB size is at most `resolution_lux`.
+    /// The LSB size does not depend on the integration time. Instead, the
+    /// longest integration time whose maximum conversion time fits within
+    /// `latency_budget_us` microseconds is recommended, since longer
+    /// integration times reduce the noise.
+    /// See [`PowerEstimate::new()`](crate::PowerEstimate::new).
+    ///
+    /// Returns `None` if `max_lux` exceeds the highest full-scale value,
+    /// if the resolution cannot be achieved in the selected range or if
+    /// no conversion fits within the latency budget.
+    pub fn plan(max_lux: f32, resolution_lux: f32, latency_budget_us: u32) -> Option<RangePlan> {
+        let exponent = (0..=EXPONENT_MAX).find(|e| full_scale(*e) >= max_lux)?;
+        if lsb(exponent) > resolution_lux {
+            return None;
+        }
+        let lux_range = LuxRange::Manual(exponent);
+        let integration_time = [IntegrationTime::Ms800, IntegrationTime::Ms100]
+            .iter()
+            .copied()
+            .find(|integration_time| {
+                let estimate =
+                    PowerEstimate::new(*integration_time, lux_range, MeasurementMode::OneShot, 0);
+                estimate.max_conversion_time_us <= latency_budget_us
+            })?;
+        Some(RangePlan {
+            lux_range,
+            integration_time,
+        })
+    }
+
+    fn manual_exponent(self) -> Option<u8> {
+        match self {
+            LuxRange::Manual(rn) if rn <= EXPONENT_MAX => Some(rn),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_get_range_values() {
+        assert_eq!(Some(0.01), LuxRange::LUX_40_95.lsb());
+        assert_eq!(Some(20.48), LuxRange::LUX_83865_60.lsb());
+        let full_scale = LuxRange::LUX_2620_80.full_scale().unwrap();
+        assert!((full_scale - 2620.8).abs() < 0.01);
+        assert_eq!(None, LuxRange::Auto.full_scale());
+        assert_eq!(None, LuxRange::Manual(12).lsb());
+    }
+
+    #[test]
+    fn plans_lowest_covering_range() {
+        let plan = LuxRange::plan(100.0, 0.1, 1_000_000).unwrap();
+        assert_eq!(LuxRange::LUX_163_80, plan.lux_range);
+    }
+
+    #[test]
+    fn plans_long_integration_time_if_latency_allows() {
+        let plan = LuxRange::plan(40.0, 0.01, 880_000).unwrap();
+        assert_eq!(LuxRange::LUX_40_95, plan.lux_range);
+        assert_eq!(IntegrationTime::Ms800, plan.integration_time);
+    }
+
+    #[test]
+    fn plans_short_integration_time_for_low_latency() {
+        let plan = LuxRange::plan(40.0, 0.01, 879_999).unwrap();
+        assert_eq!(LuxRange::LUX_40_95, plan.lux_range);
+        assert_eq!(IntegrationTime::Ms100, plan.integration_time);
+        assert!(LuxRange::plan(40.0, 0.01, 110_000).is_some());
+    }
+
+    #[test]
+    fn cannot_plan_impossible_ranges() {
+        assert_eq!(None, LuxRange::plan(100_000.0, 100.0, 1_000_000));
+        assert_eq!(None, LuxRange::plan(5000.0, 0.1, 1_000_000));
+        assert_eq!(None, LuxRange::plan(40.0, 0.005, 1_000_000));
+        assert_eq!(None, LuxRange::plan(40.0, 0.01, 109_999));
+    }
+}
@@ -0,0 +1,120 @@
+//! Measurement information implementation
+use crate::MeasurementInfo;
+
+/// Size of the LSB in lux for the exponent 0
+const LSB_LUX: f32 = 0.01;
+/// Maximum exponent value
+pub(crate) const EXPONENT_MAX: u8 = 0b1011;
+/// Maximum mantissa value
+pub(crate) const MANTISSA_MAX: u16 = 0xFFF;
+
+impl MeasurementInfo {
+    /// Full-scale range of the conversion in lux.
+    ///
+    /// This is the maximum value that could have been reported with the
+    /// exponent of the result. e.g. 40.95 lux for exponent 0.
+    pub fn full_scale(&self) -> f32 {
+        full_scale(self.exponent)
+    }
+
+    /// Size of the least significant bit (LSB) of the result in lux.
+    pub fn lsb(&self) -> f32 {
+        lsb(self.exponent)
+    }
+
+    /// Quantization uncertainty bound of the result in lux.
+    ///
+    /// This is half an LSB. The LSB size only depends on the exponent,
+    /// so this is the same for both integration times.
+    ///
+    /// Note that this does not include the device noise, gain and linearity
+    /// errors. Please see the device datasheet for further details.
+    pub fn uncertainty(&self) -> f32 {
+        lsb(self.exponent) / 2.0
+    }
+}
+
+/// Full-scale range in lux for an exponent
+pub(crate) fn full_scale(exponent: u8) -> f32 {
+    lsb(exponent) * f32::from(MANTISSA_MAX)
+}
+
+/// LSB size in lux for an exponent
+pub(crate) fn lsb(exponent: u8) -> f32 {
+    LSB_LUX * (1_u32 << exponent.min(EXPONENT_MAX)) as f32
+}
+
+/// Convert a value in lux to raw format (exponent, mantissa).
+///
+/// The lowest exponent able to represent the value is used.
+/// Returns `None` for negative values or values above the maximum full-scale.
+pub(crate) fn lux_to_raw(lux: f32) -> Option<(u8, u16)> {
+    if lux.is_nan() || lux < 0.0 {
+        return None;
+    }
+    (0..=EXPONENT_MAX).find_map(|exponent| {
+        let mantissa = lux / lsb(exponent) + 0.5;
+        if mantissa < f32::from(MANTISSA_MAX) + 1.0 {
+            Some((exponent, mantissa as u16))
+        } else {
+            None
+        }
+    })
+}
+
+/// Convert a raw value (exponent, mantissa) to lux
+pub(crate) fn raw_to_lux(result: (u8, u16)) -> f32 {
+    (f64::from(1 << result.0) * 0.01 * f64::from(result.1)) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IntegrationTime;
+
+    fn info(exponent: u8, integration_time: IntegrationTime) -> MeasurementInfo {
+        MeasurementInfo {
+            exponent,
+            integration_time,
+        }
+    }
+
+    fn assert_near(expected: f32, value: f32) {
+        assert!((expected - value).abs() < expected * 1e-4);
+    }
+
+    #[test]
+    fn can_calculate_lowest_range() {
+        let info = info(0, IntegrationTime::Ms800);
+        assert_near(40.95, info.full_scale());
+        assert_near(0.01, info.lsb());
+        assert_near(0.005, info.uncertainty());
+    }
+
+    #[test]
+    fn can_calculate_highest_range() {
+        let info = info(11, IntegrationTime::Ms800);
+        assert_near(83_865.6, info.full_scale());
+        assert_near(20.48, info.lsb());
+        assert_near(10.24, info.uncertainty());
+    }
+
+    #[test]
+    fn can_convert_lux_to_raw() {
+        assert_eq!(Some((0, 0)), lux_to_raw(0.0));
+        assert_eq!(Some((0, 4095)), lux_to_raw(40.95));
+        assert_eq!(Some((1, 2048)), lux_to_raw(40.96));
+        assert_eq!(Some((7, 0x89A)), lux_to_raw(2818.56));
+        assert_eq!(Some((11, 4095)), lux_to_raw(83_865.6));
+        assert_eq!(None, lux_to_raw(83_900.0));
+        assert_eq!(None, lux_to_raw(-1.0));
+        assert_eq!(None, lux_to_raw(f32::NAN));
+    }
+
+    #[test]
+    fn uncertainty_does_not_depend_on_integration_time() {
+        let info = info(2, IntegrationTime::Ms100);
+        assert_near(0.04, info.lsb());
+        assert_near(0.02, info.uncertainty());
+    }
+}
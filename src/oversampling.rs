@@ -0,0 +1,140 @@
+//! Low-light oversampling implementation
+use crate::{
+    measurement::lsb, mode, Error, IntegrationTime, LuxRange, Opt300x, OversampledMeasurement,
+    Oversampler,
+};
+use embedded_hal::i2c;
+
+/// Minimum number of conversions needed to estimate the noise
+const MIN_SAMPLES: u32 = 2;
+
+impl Oversampler {
+    /// Create a new instance.
+    ///
+    /// Conversions are averaged until the standard error of the mean is
+    /// at most `target_precision` times the mean (e.g. 0.01 for 1%) or
+    /// until `max_samples` conversions have been taken.
+    pub fn new(target_precision: f32, max_samples: u32) -> Self {
+        Oversampler {
+            target_precision,
+            max_samples,
+            count: 0,
+            mean: 0.0,
+            m2: 0.0,
+        }
+    }
+
+    /// Start oversampling.
+    ///
+    /// This sets the 800 ms integration time and the lowest manual lux range
+    /// (40.95 lux full-scale) and discards any previous conversions.
+    ///
+    /// Returns `Error::InvalidInputData` if the target precision is not
+    /// positive or if the maximum number of samples is lower than two.
+    ///
+    /// Note that the conversion ready flag is cleared automatically
+    /// after calling this method.
+    pub fn start<I2C, IC>(
+        &mut self,
+        sensor: &mut Opt300x<I2C, IC, mode::Continuous>,
+    ) -> Result<(), Error<I2C::Error>>
+    where
+        I2C: i2c::I2c,
+    {
+        if self.target_precision.is_nan()
+            || self.target_precision <= 0.0
+            || self.max_samples < MIN_SAMPLES
+        {
+            return Err(Error::InvalidInputData);
+        }
+        sensor.set_integration_time(IntegrationTime::Ms800)?;
+        sensor.set_lux_range(LuxRange::LUX_40_95)?;
+        self.count = 0;
+        self.mean = 0.0;
+        self.m2 = 0.0;
+        Ok(())
+    }
+
+    /// Take the next conversion into account.
+    ///
+    /// This returns `nb::Error::WouldBlock` until enough conversions have been
+    /// averaged. Conversions performed with a previous configuration
+    /// are discarded. See [`Measurement::stale`](crate::Measurement::stale).
+    ///
+    /// The quantization noise of the result is included in the noise estimation
+    /// so that a constant reading does not appear to be exact.
+    pub fn poll<I2C, IC>(
+        &mut self,
+        sensor: &mut Opt300x<I2C, IC, mode::Continuous>,
+    ) -> nb::Result<OversampledMeasurement, Error<I2C::Error>>
+    where
+        I2C: i2c::I2c,
+    {
+        let measurement = sensor.wait_for_valid_conversion()?;
+        self.add(measurement.result);
+        let result = self.result(measurement.status.has_overflown);
+        let is_precise = result.std_error <= self.target_precision * result.mean;
+        if result.has_overflown
+            || self.count >= self.max_samples
+            || (self.count >= MIN_SAMPLES && is_precise)
+        {
+            Ok(result)
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    fn add(&mut self, lux: f32) {
+        self.count += 1;
+        let lux = f64::from(lux);
+        let delta = lux - self.mean;
+        self.mean += delta / f64::from(self.count);
+        self.m2 += delta * (lux - self.mean);
+    }
+
+    fn result(&self, has_overflown: bool) -> OversampledMeasurement {
+        let variance = if self.count < MIN_SAMPLES {
+            0.0
+        } else {
+            self.m2 / f64::from(self.count - 1)
+        };
+        let lsb = f64::from(lsb(0));
+        let quantization_variance = lsb * lsb / 12.0;
+        let std_dev = libm::sqrt(variance + quantization_variance);
+        OversampledMeasurement {
+            mean: self.mean as f32,
+            std_dev: std_dev as f32,
+            std_error: (std_dev / libm::sqrt(f64::from(self.count))) as f32,
+            samples: self.count,
+            has_overflown,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_calculate_statistics() {
+        let mut oversampler = Oversampler::new(0.01, 10);
+        for lux in [0.02, 0.04, 0.06].iter() {
+            oversampler.add(*lux);
+        }
+        let result = oversampler.result(false);
+        assert_eq!(3, result.samples);
+        assert!((result.mean - 0.04).abs() < 1e-6);
+        let expected = libm::sqrtf(0.0004 + 0.0001 / 12.0);
+        assert!((result.std_dev - expected).abs() < 1e-6);
+        assert!((result.std_error - expected / libm::sqrtf(3.0)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn constant_reading_has_quantization_noise() {
+        let mut oversampler = Oversampler::new(0.01, 10);
+        oversampler.add(0.05);
+        oversampler.add(0.05);
+        let result = oversampler.result(false);
+        assert!(result.std_dev > 0.0028 && result.std_dev < 0.0029);
+    }
+}
@@ -0,0 +1,128 @@
+//! Power gating implementation
+use crate::{Opt300x, PowerError, PoweredDown, PoweredUp};
+use core::ops::{Deref, DerefMut};
+use embedded_hal::{
+    delay::DelayNs,
+    digital::{ErrorType, OutputPin},
+    i2c,
+};
+
+/// Conservative time from enabling the supply until the device accepts
+/// I²C communication in microseconds
+const POWER_ON_DELAY_US: u32 = 10_000;
+
+type PowerResult<T, I2C, PIN, DEV> =
+    Result<T, PowerError<<I2C as i2c::ErrorType>::Error, <PIN as ErrorType>::Error, DEV>>;
+
+impl<I2C, IC, MODE> Opt300x<I2C, IC, MODE> {
+    /// Take ownership of the supply-enable pin of the device.
+    ///
+    /// The device must currently be powered. A high pin level enables the supply.
+    pub fn with_power_pin<PIN>(self, pin: PIN) -> PoweredUp<I2C, IC, MODE, PIN> {
+        PoweredUp::new(self, pin)
+    }
+}
+
+impl<I2C, IC, MODE, PIN> PoweredUp<I2C, IC, MODE, PIN> {
+    /// Create a new instance.
+    ///
+    /// The device must currently be powered. A high pin level enables the supply.
+    pub fn new(sensor: Opt300x<I2C, IC, MODE>, pin: PIN) -> Self {
+        PoweredUp { sensor, pin }
+    }
+
+    /// Destroy the instance and return the driver and the pin.
+    pub fn destroy(self) -> (Opt300x<I2C, IC, MODE>, PIN) {
+        (self.sensor, self.pin)
+    }
+}
+
+impl<I2C, IC, MODE, PIN> PoweredUp<I2C, IC, MODE, PIN>
+where
+    I2C: i2c::I2c,
+    PIN: OutputPin,
+{
+    /// Power the device down by disabling its supply.
+    ///
+    /// The conversion in progress, if any, is lost. The configuration
+    /// is kept in the driver.
+    pub fn power_down(mut self) -> PowerResult<PoweredDown<I2C, IC, MODE, PIN>, I2C, PIN, Self> {
+        if let Err(e) = self.pin.set_low() {
+            return Err(PowerError::Pin(e, self));
+        }
+        self.sensor.forget_conversion();
+        Ok(PoweredDown {
+            sensor: self.sensor,
+            pin: self.pin,
+        })
+    }
+}
+
+impl<I2C, IC, MODE, PIN> Deref for PoweredUp<I2C, IC, MODE, PIN> {
+    type Target = Opt300x<I2C, IC, MODE>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.sensor
+    }
+}
+
+impl<I2C, IC, MODE, PIN> DerefMut for PoweredUp<I2C, IC, MODE, PIN> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.sensor
+    }
+}
+
+impl<I2C, IC, MODE, PIN> PoweredDown<I2C, IC, MODE, PIN> {
+    /// Create a new instance.
+    ///
+    /// The device must currently be unpowered. A high pin level enables the supply.
+    pub fn new(sensor: Opt300x<I2C, IC, MODE>, pin: PIN) -> Self {
+        PoweredDown { sensor, pin }
+    }
+
+    /// Destroy the instance and return the driver and the pin.
+    pub fn destroy(self) -> (Opt300x<I2C, IC, MODE>, PIN) {
+        (self.sensor, self.pin)
+    }
+}
+
+impl<I2C, IC, MODE, PIN> PoweredDown<I2C, IC, MODE, PIN>
+where
+    I2C: i2c::I2c,
+    PIN: OutputPin,
+{
+    /// Power the device up by enabling its supply.
+    ///
+    /// This waits until the device is ready and then writes the limits and
+    /// the configuration cached in the driver to the device, since it starts
+    /// with the default values.
+    ///
+    /// In case of an I²C error, the device is powered down again.
+    pub fn power_up<D: DelayNs>(
+        mut self,
+        delay: &mut D,
+    ) -> PowerResult<PoweredUp<I2C, IC, MODE, PIN>, I2C, PIN, Self> {
+        if let Err(e) = self.pin.set_high() {
+            return Err(PowerError::Pin(e, self));
+        }
+        delay.delay_us(POWER_ON_DELAY_US);
+        if let Err(crate::Error::I2C(e)) = self.sensor.restore_registers() {
+            // Retrying power_up() rewrites all the registers even if the supply
+            // could not be cut, so only the I²C error is reported.
+            let _ = self.pin.set_low();
+            return Err(PowerError::I2C(e, self));
+        }
+        Ok(PoweredUp {
+            sensor: self.sensor,
+            pin: self.pin,
+        })
+    }
+}
+
+impl<I2C, IC, MODE, PIN> Deref for PoweredDown<I2C, IC, MODE, PIN> {
+    type Target = Opt300x<I2C, IC, MODE>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.sensor
+    }
+}
@@ -0,0 +1,127 @@
+//! Runtime measurement mode implementation
+use crate::{
+    device_impl::to_lux_measurement, mode, Clock, Error, Measurement, MeasurementMode,
+    ModeChangeError, Opt300x,
+};
+use embedded_hal::i2c;
+
+impl<I2C, IC> Opt300x<I2C, IC, mode::OneShot> {
+    /// Change into runtime measurement mode selection.
+    ///
+    /// The device is not accessed and stays in one-shot mode.
+    /// See [`set_mode()`](Opt300x::set_mode).
+    pub fn into_runtime_mode(self) -> Opt300x<I2C, IC, mode::Runtime> {
+        self.with_mode()
+    }
+}
+
+impl<I2C, IC> Opt300x<I2C, IC, mode::Continuous> {
+    /// Change into runtime measurement mode selection.
+    ///
+    /// The device is not accessed and stays in continuous mode.
+    /// See [`set_mode()`](Opt300x::set_mode).
+    pub fn into_runtime_mode(self) -> Opt300x<I2C, IC, mode::Runtime> {
+        self.with_mode()
+    }
+}
+
+impl<I2C, IC> Opt300x<I2C, IC, mode::Runtime> {
+    /// Current measurement mode.
+    pub fn measurement_mode(&self) -> MeasurementMode {
+        self.desired_config().measurement_mode()
+    }
+}
+
+impl<I2C, IC> Opt300x<I2C, IC, mode::Runtime>
+where
+    I2C: i2c::I2c,
+{
+    /// Set the measurement mode.
+    ///
+    /// Nothing is done if the device is already in the given mode.
+    /// Otherwise, the conversion in progress, if any, is discarded.
+    /// Changing into one-shot mode shuts down the device until a
+    /// measurement is requested.
+    ///
+    /// In case of an error the measurement mode is unchanged.
+    ///
+    /// Note that the conversion ready flag is cleared automatically
+    /// after calling this method.
+    pub fn set_mode(&mut self, mode: MeasurementMode) -> Result<(), Error<I2C::Error>> {
+        if mode == self.measurement_mode() {
+            return Ok(());
+        }
+        self.apply_measurement_mode(mode)
+    }
+
+    /// Read the result of a light to digital conversion in lux
+    /// together with its status and information.
+    ///
+    /// In one-shot mode this starts a conversion if none is in progress and
+    /// returns `nb::Error::WouldBlock` until it is ready.
+    /// See [`read_lux()`](Opt300x::read_lux).
+    ///
+    /// In continuous mode this returns `nb::Error::WouldBlock` until a new
+    /// conversion is ready.
+    /// See [`read_measurement()`](Opt300x::read_measurement).
+    ///
+    /// Note that the conversion ready flag is cleared automatically
+    /// after calling this method.
+    pub fn read_measurement(&mut self) -> nb::Result<Measurement<f32>, Error<I2C::Error>> {
+        self.read_measurement_in_mode(None)
+    }
+
+    /// Read the result of a light to digital conversion in lux
+    /// together with its status, information and completion time.
+    ///
+    /// This works like [`read_measurement()`](Opt300x::read_measurement)
+    /// and additionally estimates the time at which the conversion was
+    /// completed.
+    /// See [`read_lux_timestamped()`](Opt300x::read_lux_timestamped) and
+    /// [`read_measurement_timestamped()`](Opt300x::read_measurement_timestamped).
+    pub fn read_measurement_timestamped<CLK: Clock>(
+        &mut self,
+        clock: &mut CLK,
+    ) -> nb::Result<Measurement<f32>, Error<I2C::Error>> {
+        self.read_measurement_in_mode(Some(clock))
+    }
+
+    /// Change into one-shot mode with the mode checked at compile time.
+    ///
+    /// This will actually shut down the device until a measurement is
+    /// requested, unless it is already in one-shot mode.
+    pub fn into_one_shot(
+        mut self,
+    ) -> Result<Opt300x<I2C, IC, mode::OneShot>, ModeChangeError<I2C::Error, Self>> {
+        if let Err(Error::I2C(e)) = self.set_mode(MeasurementMode::OneShot) {
+            return Err(ModeChangeError::I2C(e, self));
+        }
+        Ok(self.with_mode())
+    }
+
+    /// Change into continuous mode with the mode checked at compile time.
+    ///
+    /// Note that the conversion ready flag is cleared automatically
+    /// after calling this method.
+    pub fn into_continuous(
+        mut self,
+    ) -> Result<Opt300x<I2C, IC, mode::Continuous>, ModeChangeError<I2C::Error, Self>> {
+        if let Err(Error::I2C(e)) = self.set_mode(MeasurementMode::Continuous) {
+            return Err(ModeChangeError::I2C(e, self));
+        }
+        Ok(self.with_mode())
+    }
+
+    fn read_measurement_in_mode(
+        &mut self,
+        clock: Option<&mut dyn Clock>,
+    ) -> nb::Result<Measurement<f32>, Error<I2C::Error>> {
+        match self.measurement_mode() {
+            MeasurementMode::Continuous => self.read_next_measurement(clock),
+            MeasurementMode::OneShot => {
+                let measurement = self.read_one_shot(clock)?;
+                Ok(to_lux_measurement(measurement))
+            }
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! Duty-cycled periodic sampling implementation
+use crate::{mode, Clock, Error, Measurement, Opt300x, PeriodicSampler, Sample};
+use core::convert::TryFrom;
+use embedded_hal::{delay::DelayNs, i2c};
+
+/// Time between polls of a conversion which is not ready yet in microseconds
+const POLL_INTERVAL_US: u32 = 10_000;
+
+impl PeriodicSampler {
+    /// Create a new instance taking one sample every `period_us` microseconds.
+    pub fn new(period_us: u64) -> Self {
+        PeriodicSampler {
+            period_us: period_us.max(1),
+            next_deadline: None,
+            missed_deadlines: 0,
+        }
+    }
+
+    /// Total number of missed deadlines.
+    pub fn missed_deadlines(&self) -> u32 {
+        self.missed_deadlines
+    }
+
+    /// Time of the next sample in microseconds.
+    ///
+    /// Returns `None` before the first sample.
+    pub fn next_deadline(&self) -> Option<u64> {
+        self.next_deadline
+    }
+
+    /// Perform a sampling cycle.
+    ///
+    /// This waits until the next deadline, triggers a one-shot conversion,
+    /// waits for the maximum conversion time and reads the result.
+    /// The first sample is taken immediately.
+    /// The device shuts down automatically after a one-shot conversion, so
+    /// it stays in shutdown between samples.
+    ///
+    /// If this is called after the next deadline, the sample is taken
+    /// immediately and the deadlines of the periods which elapsed entirely
+    /// are reported as missed.
+    ///
+    /// If the conversion does not complete within twice the maximum
+    /// conversion time, the device is explicitly shut down and the sample
+    /// contains no measurement.
+    /// If a bus error occurs, the device is shut down if possible and the
+    /// error is returned. The next call performs a new cycle in any case.
+    pub fn sample<I2C, IC, D, CLK>(
+        &mut self,
+        sensor: &mut Opt300x<I2C, IC, mode::OneShot>,
+        delay: &mut D,
+        clock: &mut CLK,
+    ) -> Result<Sample, Error<I2C::Error>>
+    where
+        I2C: i2c::I2c,
+        D: DelayNs,
+        CLK: Clock,
+    {
+        let now = clock.now_us();
+        let mut deadline = self.next_deadline.unwrap_or(now);
+        let mut missed_deadlines = 0;
+        if now >= deadline + self.period_us {
+            let missed = (now - deadline) / self.period_us;
+            deadline += missed * self.period_us;
+            missed_deadlines = u32::try_from(missed).unwrap_or(u32::MAX);
+            self.missed_deadlines = self.missed_deadlines.saturating_add(missed_deadlines);
+        } else if now < deadline {
+            wait_us(delay, deadline - now);
+        }
+        self.next_deadline = Some(deadline + self.period_us);
+        let measurement = match read(sensor, delay, clock) {
+            Ok(Some(measurement)) => Some(measurement),
+            Ok(None) => {
+                sensor.shut_down()?;
+                None
+            }
+            Err(e) => {
+                // The shutdown most likely fails on the same bus, and a conversion
+                // left running completes and shuts the device down by itself.
+                let _ = sensor.shut_down();
+                return Err(e);
+            }
+        };
+        Ok(Sample {
+            measurement,
+            deadline,
+            missed_deadlines,
+        })
+    }
+}
+
+/// Trigger a conversion and wait for its result
+fn read<I2C, IC, D, CLK>(
+    sensor: &mut Opt300x<I2C, IC, mode::OneShot>,
+    delay: &mut D,
+    clock: &mut CLK,
+) -> Result<Option<Measurement<f32>>, Error<I2C::Error>>
+where
+    I2C: i2c::I2c,
+    D: DelayNs,
+    CLK: Clock,
+{
+    let max_conversion_time_us = sensor.power_estimate(0).max_conversion_time_us;
+    let token = sensor.start_conversion()?;
+    let started_at = clock.now_us();
+    delay.delay_us(max_conversion_time_us);
+    let timeout = started_at + 2 * u64::from(max_conversion_time_us);
+    loop {
+        if let Some(measurement) = sensor.poll(&token)? {
+            return Ok(Some(measurement));
+        }
+        if clock.now_us() >= timeout {
+            return Ok(None);
+        }
+        delay.delay_us(POLL_INTERVAL_US);
+    }
+}
+
+fn wait_us<D: DelayNs>(delay: &mut D, us: u64) {
+    let mut remaining = us;
+    while remaining > 0 {
+        let step = remaining.min(u64::from(u32::MAX));
+        delay.delay_us(step as u32);
+        remaining -= step;
+    }
+}
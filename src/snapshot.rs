@@ -0,0 +1,209 @@
+//! Configuration snapshot implementation
+use crate::{
+    device_impl::{BitFlags, Register},
+    measurement::EXPONENT_MAX,
+    Config, ConfigSnapshot, Error, Opt300x, RestoreError,
+};
+use embedded_hal::i2c;
+
+/// Version of the byte representation
+const FORMAT_VERSION: u8 = 1;
+/// Length of the byte representation
+const SNAPSHOT_SIZE: usize = 9;
+/// Configuration bits contained in a snapshot
+const CONFIG_MASK: u16 = !(BitFlags::MODE1
+    | BitFlags::MODE0
+    | BitFlags::OVF
+    | BitFlags::CRF
+    | BitFlags::FH
+    | BitFlags::FL);
+/// Highest valid range number field value
+const RN_MAX: u16 = 0b1100;
+
+impl ConfigSnapshot {
+    /// Length of the byte representation
+    pub const SIZE: usize = SNAPSHOT_SIZE;
+
+    /// Byte representation of the snapshot.
+    ///
+    /// This contains a format version, the register values in big-endian
+    /// order and a CRC-16/CCITT-FALSE checksum over the preceding bytes.
+    pub fn to_bytes(&self) -> [u8; SNAPSHOT_SIZE] {
+        let mut data = [0; SNAPSHOT_SIZE];
+        data[0] = FORMAT_VERSION;
+        data[1..3].copy_from_slice(&self.config.to_be_bytes());
+        data[3..5].copy_from_slice(&self.low_limit.to_be_bytes());
+        data[5..7].copy_from_slice(&self.high_limit.to_be_bytes());
+        let crc = crc16(&data[..SNAPSHOT_SIZE - 2]);
+        data[SNAPSHOT_SIZE - 2..].copy_from_slice(&crc.to_be_bytes());
+        data
+    }
+
+    /// Create a snapshot from its byte representation.
+    ///
+    /// Returns `None` if the length, format version or checksum is wrong
+    /// or if a register value is invalid.
+    /// See [`to_bytes()`](ConfigSnapshot::to_bytes).
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        if data.len() != SNAPSHOT_SIZE || data[0] != FORMAT_VERSION {
+            return None;
+        }
+        let crc = u16::from_be_bytes([data[SNAPSHOT_SIZE - 2], data[SNAPSHOT_SIZE - 1]]);
+        if crc != crc16(&data[..SNAPSHOT_SIZE - 2]) {
+            return None;
+        }
+        let snapshot = ConfigSnapshot {
+            config: u16::from_be_bytes([data[1], data[2]]),
+            low_limit: u16::from_be_bytes([data[3], data[4]]),
+            high_limit: u16::from_be_bytes([data[5], data[6]]),
+        };
+        if snapshot.is_valid() {
+            Some(snapshot)
+        } else {
+            None
+        }
+    }
+
+    /// Whether the end-of-conversion mode is enabled.
+    pub fn is_end_of_conversion_mode_enabled(&self) -> bool {
+        is_end_of_conversion_mode(self.low_limit)
+    }
+
+    fn is_valid(&self) -> bool {
+        let is_low_limit_valid =
+            self.is_end_of_conversion_mode_enabled() || is_limit_valid(self.low_limit);
+        self.config & !CONFIG_MASK == 0
+            && is_range_valid(self.config)
+            && is_low_limit_valid
+            && is_limit_valid(self.high_limit)
+    }
+}
+
+impl<I2C, IC, MODE> Opt300x<I2C, IC, MODE>
+where
+    I2C: i2c::I2c,
+{
+    /// Read a snapshot of the configuration from the device.
+    ///
+    /// The status flags read from the device are recorded as events and
+    /// a conversion ready flag is not lost for the measurement reading methods.
+    pub fn snapshot(&mut self) -> Result<ConfigSnapshot, Error<I2C::Error>> {
+        Ok(ConfigSnapshot {
+            config: self.read_config_register()? & CONFIG_MASK,
+            low_limit: self.read_register(Register::LOW_LIMIT)?,
+            high_limit: self.read_register(Register::HIGH_LIMIT)?,
+        })
+    }
+
+    /// Write a configuration snapshot to the device and verify it.
+    ///
+    /// The current measurement mode is kept. A one-shot conversion in
+    /// progress is aborted. The cached configuration and limits are updated.
+    ///
+    /// Returns `RestoreError::VerificationFailed` if the registers read back
+    /// from the device differ from the snapshot.
+    pub fn restore(&mut self, snapshot: &ConfigSnapshot) -> Result<(), RestoreError<I2C::Error>> {
+        let end_of_conversion_mode = snapshot.is_end_of_conversion_mode_enabled();
+        let low_limit = if end_of_conversion_mode {
+            snapshot.low_limit & 0x3FFF
+        } else {
+            snapshot.low_limit
+        };
+        self.write_low_limit(low_limit, end_of_conversion_mode)
+            .map_err(restore_error)?;
+        self.write_register(Register::HIGH_LIMIT, snapshot.high_limit)
+            .map_err(restore_error)?;
+        self.state.high_limit = snapshot.high_limit;
+        let mode = self.desired_config().measurement_mode();
+        let config = Config {
+            bits: snapshot.config,
+        };
+        self.apply_config_in_mode(config, mode)
+            .map_err(restore_error)?;
+
+        let read_back = self.snapshot().map_err(restore_error)?;
+        if read_back == *snapshot {
+            Ok(())
+        } else {
+            Err(RestoreError::VerificationFailed)
+        }
+    }
+}
+
+fn is_end_of_conversion_mode(low_limit: u16) -> bool {
+    low_limit >> 14 == 0b11
+}
+
+/// Whether the range number field of a configuration register value is valid
+pub(crate) fn is_range_valid(config: u16) -> bool {
+    config >> 12 <= RN_MAX
+}
+
+/// Whether the exponent of a limit register value is valid
+pub(crate) fn is_limit_valid(limit: u16) -> bool {
+    limit >> 12 <= u16::from(EXPONENT_MAX)
+}
+
+/// Only I²C errors can occur while writing and reading back the registers
+fn restore_error<E>(error: Error<E>) -> RestoreError<E> {
+    match error {
+        Error::I2C(e) => RestoreError::I2C(e),
+        Error::InvalidInputData => RestoreError::VerificationFailed,
+    }
+}
+
+/// CRC-16/CCITT-FALSE
+pub(crate) fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for byte in data {
+        crc ^= u16::from(*byte) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_calculate_crc() {
+        assert_eq!(0x29B1, crc16(b"123456789"));
+    }
+
+    #[test]
+    fn rejects_invalid_register_values() {
+        let snapshot = ConfigSnapshot {
+            config: 0xC810,
+            low_limit: 0,
+            high_limit: 0xBFFF,
+        };
+        assert!(snapshot.is_valid());
+        assert!(!ConfigSnapshot {
+            config: 0xD810,
+            ..snapshot
+        }
+        .is_valid());
+        assert!(!ConfigSnapshot {
+            config: 0xC810 | BitFlags::CRF,
+            ..snapshot
+        }
+        .is_valid());
+        assert!(!ConfigSnapshot {
+            high_limit: 0xC000,
+            ..snapshot
+        }
+        .is_valid());
+        assert!(ConfigSnapshot {
+            low_limit: 0xC000,
+            ..snapshot
+        }
+        .is_valid());
+    }
+}
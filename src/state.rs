@@ -0,0 +1,374 @@
+//! Driver state decomposition and serialization
+use crate::{
+    marker, mode,
+    snapshot::{crc16, is_limit_valid, is_range_valid},
+    Config, ConfigChange, ConversionTimes, DriverState, Events, InFlightConfigPolicy,
+    MeasurementMode, Opt300x, PhantomData, Status,
+};
+use core::fmt;
+
+/// Version of the byte representation
+const FORMAT_VERSION: u8 = 1;
+/// Length of the byte representation
+const STATE_SIZE: usize = 51;
+
+struct Flag;
+impl Flag {
+    const END_OF_CONVERSION_MODE: u16 = 1;
+    const WAS_CONVERSION_STARTED: u16 = 1 << 1;
+    const CONVERSION_READY_PENDING: u16 = 1 << 2;
+    const DEFER_CONFIG: u16 = 1 << 3;
+    const PENDING_CONFIG: u16 = 1 << 4;
+    const CONVERSION_PERIOD_SCALE: u16 = 1 << 5;
+    const STARTED_AT: u16 = 1 << 6;
+    const NOT_READY_AT: u16 = 1 << 7;
+    const LAST_END: u16 = 1 << 8;
+    const ALL: u16 = (1 << 9) - 1;
+}
+
+impl marker::Mode for mode::OneShot {
+    fn allows(mode: MeasurementMode) -> bool {
+        mode == MeasurementMode::OneShot
+    }
+}
+
+impl marker::Mode for mode::Continuous {
+    fn allows(mode: MeasurementMode) -> bool {
+        mode == MeasurementMode::Continuous
+    }
+}
+
+impl marker::Mode for mode::Runtime {
+    fn allows(_mode: MeasurementMode) -> bool {
+        true
+    }
+}
+
+impl<I2C, IC, MODE> Opt300x<I2C, IC, MODE> {
+    /// Destroy the driver instance and return the I²C bus instance together
+    /// with the cached state of the driver.
+    ///
+    /// The device is not accessed. The driver can be rebuilt later on with
+    /// [`from_parts()`](Opt300x::from_parts) as long as the device
+    /// is not accessed by other means in between.
+    pub fn into_parts(self) -> (I2C, DriverState<IC, MODE>) {
+        (self.i2c, self.state)
+    }
+
+    /// Rebuild a driver instance from an I²C bus instance and the state
+    /// returned by [`into_parts()`](Opt300x::into_parts).
+    ///
+    /// The device is not accessed.
+    pub fn from_parts(i2c: I2C, state: DriverState<IC, MODE>) -> Self {
+        Opt300x { i2c, state }
+    }
+}
+
+impl<IC, MODE> DriverState<IC, MODE> {
+    /// Length of the byte representation
+    pub const SIZE: usize = STATE_SIZE;
+
+    /// Same state for another measurement mode marker.
+    pub(crate) fn into_mode<NEWMODE>(self) -> DriverState<IC, NEWMODE> {
+        DriverState {
+            address: self.address,
+            config: self.config,
+            low_limit: self.low_limit,
+            high_limit: self.high_limit,
+            end_of_conversion_mode: self.end_of_conversion_mode,
+            was_conversion_started: self.was_conversion_started,
+            conversion_period_scale: self.conversion_period_scale,
+            conversion_times: self.conversion_times,
+            config_generation: self.config_generation,
+            conversion_generation: self.conversion_generation,
+            conversion_id: self.conversion_id,
+            in_flight_config_policy: self.in_flight_config_policy,
+            pending_config: self.pending_config,
+            last_config_change: self.last_config_change,
+            pending_events: self.pending_events,
+            last_status: self.last_status,
+            conversion_ready_pending: self.conversion_ready_pending,
+            _ic: PhantomData,
+            _mode: PhantomData,
+        }
+    }
+
+    /// Byte representation of the state.
+    ///
+    /// The representation is versioned, ends with a CRC-16/CCITT-FALSE
+    /// checksum over the preceding bytes and is only valid for this driver.
+    pub fn to_bytes(&self) -> [u8; STATE_SIZE] {
+        let mut flags = 0;
+        let mut set_flag = |flag: u16, value: bool| {
+            if value {
+                flags |= flag;
+            }
+        };
+        set_flag(Flag::END_OF_CONVERSION_MODE, self.end_of_conversion_mode);
+        set_flag(Flag::WAS_CONVERSION_STARTED, self.was_conversion_started);
+        set_flag(
+            Flag::CONVERSION_READY_PENDING,
+            self.conversion_ready_pending,
+        );
+        set_flag(
+            Flag::DEFER_CONFIG,
+            self.in_flight_config_policy == InFlightConfigPolicy::Defer,
+        );
+        set_flag(Flag::PENDING_CONFIG, self.pending_config.is_some());
+        set_flag(
+            Flag::CONVERSION_PERIOD_SCALE,
+            self.conversion_period_scale.is_some(),
+        );
+        let times = &self.conversion_times;
+        set_flag(Flag::STARTED_AT, times.started_at.is_some());
+        set_flag(Flag::NOT_READY_AT, times.not_ready_at.is_some());
+        set_flag(Flag::LAST_END, times.last_end.is_some());
+
+        let mut data = [0; STATE_SIZE];
+        data[0] = FORMAT_VERSION;
+        data[1] = self.address;
+        data[2..4].copy_from_slice(&self.config.bits.to_be_bytes());
+        data[4..6].copy_from_slice(&self.low_limit.to_be_bytes());
+        data[6..8].copy_from_slice(&self.high_limit.to_be_bytes());
+        data[8..10].copy_from_slice(&self.config_generation.to_be_bytes());
+        data[10..12].copy_from_slice(&self.conversion_generation.to_be_bytes());
+        data[12..14].copy_from_slice(&self.conversion_id.to_be_bytes());
+        let pending_config = self.pending_config.unwrap_or_default();
+        data[14..16].copy_from_slice(&pending_config.bits.to_be_bytes());
+        let scale = self.conversion_period_scale.unwrap_or(0.0);
+        data[16..20].copy_from_slice(&scale.to_bits().to_be_bytes());
+        data[20..28].copy_from_slice(&times.started_at.unwrap_or(0).to_be_bytes());
+        data[28..36].copy_from_slice(&times.not_ready_at.unwrap_or(0).to_be_bytes());
+        data[36..44].copy_from_slice(&times.last_end.unwrap_or(0).to_be_bytes());
+        data[44] = self.pending_events.bits;
+        data[45] = status_to_bits(self.last_status);
+        data[46] = match self.last_config_change {
+            None => 0,
+            Some(ConfigChange::Applied) => 1,
+            Some(ConfigChange::ConversionRestarted) => 2,
+            Some(ConfigChange::Deferred) => 3,
+        };
+        data[47..49].copy_from_slice(&flags.to_be_bytes());
+        let crc = crc16(&data[..STATE_SIZE - 2]);
+        data[STATE_SIZE - 2..].copy_from_slice(&crc.to_be_bytes());
+        data
+    }
+}
+
+impl<IC, MODE: marker::Mode> DriverState<IC, MODE> {
+    /// Create the state from its byte representation.
+    ///
+    /// Returns `None` if the data is not a valid representation of a state
+    /// with the measurement mode of `MODE`. For example, if it was produced
+    /// by a driver in another mode or by another version of this driver,
+    /// if the checksum is wrong or if a register value is invalid.
+    ///
+    /// Note that the device type is not part of the representation.
+    /// See [`to_bytes()`](DriverState::to_bytes).
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        if data.len() != STATE_SIZE || data[0] != FORMAT_VERSION {
+            return None;
+        }
+        let u16_at = |i: usize| u16::from_be_bytes([data[i], data[i + 1]]);
+        let u64_at = |i: usize| {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(&data[i..i + 8]);
+            u64::from_be_bytes(bytes)
+        };
+        if u16_at(STATE_SIZE - 2) != crc16(&data[..STATE_SIZE - 2]) {
+            return None;
+        }
+        let flags = u16_at(47);
+        if flags & !Flag::ALL != 0 {
+            return None;
+        }
+        let has = |flag: u16| (flags & flag) != 0;
+        let when = |flag: u16, i: usize| if has(flag) { Some(u64_at(i)) } else { None };
+        let last_config_change = match data[46] {
+            0 => None,
+            1 => Some(ConfigChange::Applied),
+            2 => Some(ConfigChange::ConversionRestarted),
+            3 => Some(ConfigChange::Deferred),
+            _ => return None,
+        };
+        let config = Config { bits: u16_at(2) };
+        let pending_config = if has(Flag::PENDING_CONFIG) {
+            Some(Config { bits: u16_at(14) })
+        } else {
+            None
+        };
+        let mode = pending_config.unwrap_or(config).measurement_mode();
+        if !MODE::allows(mode) {
+            return None;
+        }
+        let (low_limit, high_limit) = (u16_at(4), u16_at(6));
+        let are_registers_valid = is_range_valid(config.bits)
+            && is_range_valid(pending_config.unwrap_or(config).bits)
+            && is_limit_valid(low_limit)
+            && is_limit_valid(high_limit);
+        if !are_registers_valid {
+            return None;
+        }
+        let scale = f32::from_bits(u32::from_be_bytes([data[16], data[17], data[18], data[19]]));
+        Some(DriverState {
+            address: data[1],
+            config,
+            low_limit,
+            high_limit,
+            end_of_conversion_mode: has(Flag::END_OF_CONVERSION_MODE),
+            was_conversion_started: has(Flag::WAS_CONVERSION_STARTED),
+            conversion_period_scale: if has(Flag::CONVERSION_PERIOD_SCALE) {
+                Some(scale)
+            } else {
+                None
+            },
+            conversion_times: ConversionTimes {
+                started_at: when(Flag::STARTED_AT, 20),
+                not_ready_at: when(Flag::NOT_READY_AT, 28),
+                last_end: when(Flag::LAST_END, 36),
+            },
+            config_generation: u16_at(8),
+            conversion_generation: u16_at(10),
+            conversion_id: u16_at(12),
+            in_flight_config_policy: if has(Flag::DEFER_CONFIG) {
+                InFlightConfigPolicy::Defer
+            } else {
+                InFlightConfigPolicy::Restart
+            },
+            pending_config,
+            last_config_change,
+            pending_events: Events::from_bits(data[44])?,
+            last_status: status_from_bits(data[45])?,
+            conversion_ready_pending: has(Flag::CONVERSION_READY_PENDING),
+            _ic: PhantomData,
+            _mode: PhantomData,
+        })
+    }
+}
+
+// Implemented manually so that the device and mode markers do not need
+// to implement these traits.
+impl<IC, MODE> Clone for DriverState<IC, MODE> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<IC, MODE> Copy for DriverState<IC, MODE> {}
+
+impl<IC, MODE> PartialEq for DriverState<IC, MODE> {
+    fn eq(&self, other: &Self) -> bool {
+        self.address == other.address
+            && self.config == other.config
+            && self.low_limit == other.low_limit
+            && self.high_limit == other.high_limit
+            && self.end_of_conversion_mode == other.end_of_conversion_mode
+            && self.was_conversion_started == other.was_conversion_started
+            && self.conversion_period_scale == other.conversion_period_scale
+            && self.conversion_times == other.conversion_times
+            && self.config_generation == other.config_generation
+            && self.conversion_generation == other.conversion_generation
+            && self.conversion_id == other.conversion_id
+            && self.in_flight_config_policy == other.in_flight_config_policy
+            && self.pending_config == other.pending_config
+            && self.last_config_change == other.last_config_change
+            && self.pending_events == other.pending_events
+            && self.last_status == other.last_status
+            && self.conversion_ready_pending == other.conversion_ready_pending
+    }
+}
+
+impl<IC, MODE> fmt::Debug for DriverState<IC, MODE> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DriverState")
+            .field("address", &self.address)
+            .field("config", &self.config)
+            .field("low_limit", &self.low_limit)
+            .field("high_limit", &self.high_limit)
+            .field("end_of_conversion_mode", &self.end_of_conversion_mode)
+            .field("was_conversion_started", &self.was_conversion_started)
+            .field("conversion_period_scale", &self.conversion_period_scale)
+            .field("conversion_times", &self.conversion_times)
+            .field("config_generation", &self.config_generation)
+            .field("conversion_generation", &self.conversion_generation)
+            .field("conversion_id", &self.conversion_id)
+            .field("in_flight_config_policy", &self.in_flight_config_policy)
+            .field("pending_config", &self.pending_config)
+            .field("last_config_change", &self.last_config_change)
+            .field("pending_events", &self.pending_events)
+            .field("last_status", &self.last_status)
+            .field("conversion_ready_pending", &self.conversion_ready_pending)
+            .finish()
+    }
+}
+
+fn status_to_bits(status: Status) -> u8 {
+    u8::from(status.has_overflown)
+        | u8::from(status.conversion_ready) << 1
+        | u8::from(status.was_too_high) << 2
+        | u8::from(status.was_too_low) << 3
+}
+
+fn status_from_bits(bits: u8) -> Option<Status> {
+    if bits >> 4 != 0 {
+        return None;
+    }
+    Some(Status {
+        has_overflown: (bits & 1) != 0,
+        conversion_ready: (bits & 1 << 1) != 0,
+        was_too_high: (bits & 1 << 2) != 0,
+        was_too_low: (bits & 1 << 3) != 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ic, SlaveAddr};
+
+    type State = DriverState<ic::Opt3001, mode::OneShot>;
+
+    fn state() -> State {
+        Opt300x::new_opt3001((), SlaveAddr::default())
+            .into_parts()
+            .1
+    }
+
+    #[test]
+    fn rejects_unknown_flags() {
+        let mut state = state();
+        state.conversion_ready_pending = true;
+        let mut data = state.to_bytes();
+        data[47] = 0xFF;
+        let crc = crc16(&data[..STATE_SIZE - 2]);
+        data[STATE_SIZE - 2..].copy_from_slice(&crc.to_be_bytes());
+        assert_eq!(None, State::from_bytes(&data));
+    }
+
+    #[test]
+    fn rejects_invalid_register_values() {
+        assert_eq!(Some(state()), State::from_bytes(&state().to_bytes()));
+        let invalid_range = Config { bits: 0xD810 };
+        let invalid = [
+            State {
+                config: invalid_range,
+                ..state()
+            },
+            State {
+                pending_config: Some(invalid_range),
+                ..state()
+            },
+            State {
+                low_limit: 0xC000,
+                ..state()
+            },
+            State {
+                high_limit: 0xC000,
+                ..state()
+            },
+        ];
+        for state in invalid.iter() {
+            assert_eq!(None, State::from_bytes(&state.to_bytes()));
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! Step-change detection implementation
+use crate::{ChangeKind, ChangeThresholds, LightChange, StepCandidate, StepDetector};
+
+impl ChangeThresholds {
+    fn is_significant(&self, from: f32, to: f32) -> bool {
+        let change = (to - from).abs();
+        change >= self.absolute && change >= self.relative * from.max(to)
+    }
+}
+
+impl StepDetector {
+    /// Create a new instance.
+    ///
+    /// A significant change between two consecutive measurements is
+    /// classified as a step if the new level persists for at least
+    /// `min_dwell_us` microseconds. Otherwise it is discarded as a transient.
+    /// A significant change accumulated over several measurements is
+    /// classified as gradual.
+    pub fn new(thresholds: ChangeThresholds, min_dwell_us: u64) -> Self {
+        StepDetector {
+            thresholds,
+            min_dwell_us,
+            level: None,
+            previous: None,
+            candidate: None,
+        }
+    }
+
+    /// Current reference level in lux.
+    ///
+    /// This is the level after the last detected change.
+    pub fn level(&self) -> Option<f32> {
+        self.level
+    }
+
+    /// Update the detector with a measurement in lux and its timestamp in
+    /// microseconds.
+    ///
+    /// This is meant to be called with every measurement in continuous mode.
+    /// See [`read_measurement_timestamped()`](crate::Opt300x::read_measurement_timestamped).
+    ///
+    /// Returns the detected change, if any.
+    pub fn update(&mut self, lux: f32, timestamp: u64) -> Option<LightChange> {
+        let level = match self.level {
+            Some(level) => level,
+            None => {
+                self.level = Some(lux);
+                self.previous = Some(lux);
+                return None;
+            }
+        };
+        let previous = self.previous.replace(lux).unwrap_or(lux);
+        let thresholds = self.thresholds;
+        if let Some(candidate) = self.candidate {
+            if thresholds.is_significant(candidate.after, lux) {
+                self.candidate = self.step_candidate(candidate.before, lux, timestamp);
+            }
+        } else if thresholds.is_significant(previous, lux) {
+            self.candidate = self.step_candidate(level, lux, timestamp);
+        } else if thresholds.is_significant(level, lux) {
+            self.level = Some(lux);
+            return Some(LightChange {
+                kind: ChangeKind::Gradual,
+                before: level,
+                after: lux,
+                timestamp,
+            });
+        }
+        let candidate = self.candidate?;
+        if timestamp.saturating_sub(candidate.since) < self.min_dwell_us {
+            return None;
+        }
+        self.candidate = None;
+        if !thresholds.is_significant(candidate.before, lux) {
+            return None;
+        }
+        self.level = Some(lux);
+        let kind = if lux > candidate.before {
+            ChangeKind::StepOn
+        } else {
+            ChangeKind::StepOff
+        };
+        Some(LightChange {
+            kind,
+            before: candidate.before,
+            after: lux,
+            timestamp,
+        })
+    }
+
+    /// A step candidate if the level changed significantly
+    fn step_candidate(&self, before: f32, after: f32, since: u64) -> Option<StepCandidate> {
+        if self.thresholds.is_significant(before, after) {
+            Some(StepCandidate {
+                before,
+                after,
+                since,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THRESHOLDS: ChangeThresholds = ChangeThresholds {
+        relative: 0.5,
+        absolute: 20.0,
+    };
+
+    #[test]
+    fn detects_step_on_after_dwell_time() {
+        let mut detector = StepDetector::new(THRESHOLDS, 1_000_000);
+        assert_eq!(None, detector.update(10.0, 0));
+        assert_eq!(None, detector.update(300.0, 800_000));
+        assert_eq!(None, detector.update(305.0, 1_600_000));
+        let change = detector.update(302.0, 2_400_000).unwrap();
+        assert_eq!(ChangeKind::StepOn, change.kind);
+        assert_eq!(10.0, change.before);
+        assert_eq!(302.0, change.after);
+        assert_eq!(2_400_000, change.timestamp);
+        assert_eq!(Some(302.0), detector.level());
+    }
+
+    #[test]
+    fn detects_step_off() {
+        let mut detector = StepDetector::new(THRESHOLDS, 0);
+        detector.update(300.0, 0);
+        let change = detector.update(10.0, 800_000).unwrap();
+        assert_eq!(ChangeKind::StepOff, change.kind);
+        assert_eq!(300.0, change.before);
+    }
+
+    #[test]
+    fn ignores_transients() {
+        let mut detector = StepDetector::new(THRESHOLDS, 1_000_000);
+        detector.update(300.0, 0);
+        assert_eq!(None, detector.update(10.0, 800_000));
+        assert_eq!(None, detector.update(295.0, 1_600_000));
+        assert_eq!(None, detector.update(298.0, 2_400_000));
+        assert_eq!(Some(300.0), detector.level());
+    }
+
+    #[test]
+    fn detects_gradual_change() {
+        let mut detector = StepDetector::new(THRESHOLDS, 0);
+        detector.update(100.0, 0);
+        let mut lux = 100.0;
+        let mut change = None;
+        while change.is_none() {
+            lux += 10.0;
+            change = detector.update(lux, 0);
+        }
+        let change = change.unwrap();
+        assert_eq!(ChangeKind::Gradual, change.kind);
+        assert_eq!(100.0, change.before);
+        assert_eq!(200.0, change.after);
+    }
+}
@@ -0,0 +1,83 @@
+//! Threshold window and hysteresis implementation
+use crate::{
+    measurement::{lux_to_raw, raw_to_lux, EXPONENT_MAX, MANTISSA_MAX},
+    ComparisonMode, Error, Opt300x,
+};
+use embedded_hal::i2c;
+
+/// Limit in raw format (exponent, mantissa)
+type RawLimit = (u8, u16);
+
+/// Raw value of a limit which the result can never exceed
+const LIMIT_MAX: RawLimit = (EXPONENT_MAX, MANTISSA_MAX);
+/// Raw value of a limit which the result can never fall below
+const LIMIT_MIN: RawLimit = (0, 0);
+
+impl<I2C, IC, MODE> Opt300x<I2C, IC, MODE>
+where
+    I2C: i2c::I2c,
+{
+    /// Set a threshold window in lux.
+    ///
+    /// An interrupt will be triggered when the result falls below `low` or
+    /// rises above `high`. This sets the latched window-style comparison mode.
+    ///
+    /// The limits are written in an order such that the window never becomes
+    /// inverted, which avoids spurious interrupts while moving it.
+    ///
+    /// Returns `Error::InvalidInputData` if `low` is not lower than `high` or
+    /// if any value is outside the measurement range.
+    ///
+    /// Note that this disables the end-of-conversion mode.
+    pub fn set_window(&mut self, low: f32, high: f32) -> Result<(), Error<I2C::Error>> {
+        let (low, high) = lux_limits(low, high)?;
+        self.write_limits(low, high)?;
+        self.set_comparison_mode(ComparisonMode::LatchedWindow)
+    }
+
+    /// Set a threshold with hysteresis in lux.
+    ///
+    /// The interrupt will be asserted when the result rises above `threshold`
+    /// and deasserted when it falls below `threshold - band`.
+    /// This sets the transparent hysteresis-style comparison mode.
+    ///
+    /// Returns `Error::InvalidInputData` if `band` is not positive, greater
+    /// than `threshold` or if `threshold` is outside the measurement range.
+    ///
+    /// Note that this disables the end-of-conversion mode.
+    pub fn set_hysteresis(&mut self, threshold: f32, band: f32) -> Result<(), Error<I2C::Error>> {
+        if band.is_nan() || band <= 0.0 {
+            return Err(Error::InvalidInputData);
+        }
+        let (low, high) = lux_limits(threshold - band, threshold)?;
+        self.write_limits(low, high)?;
+        self.set_comparison_mode(ComparisonMode::TransparentHysteresis)
+    }
+
+    /// Program the limits so that the result can never be lower than
+    /// the low limit or higher than the high limit.
+    ///
+    /// The end-of-conversion mode is kept unchanged.
+    pub fn disable_thresholds(&mut self) -> Result<(), Error<I2C::Error>> {
+        self.set_high_limit_raw(LIMIT_MAX.0, LIMIT_MAX.1)?;
+        self.set_low_limit_raw_keeping_end_of_conversion_mode(LIMIT_MIN.0, LIMIT_MIN.1)
+    }
+
+    fn write_limits(&mut self, low: RawLimit, high: RawLimit) -> Result<(), Error<I2C::Error>> {
+        if raw_to_lux(low) > raw_to_lux(self.high_limit_raw()) {
+            self.set_high_limit_raw(high.0, high.1)?;
+            self.set_low_limit_raw(low.0, low.1)
+        } else {
+            self.set_low_limit_raw(low.0, low.1)?;
+            self.set_high_limit_raw(high.0, high.1)
+        }
+    }
+}
+
+/// Convert window limits in lux to distinct raw limits
+pub(crate) fn lux_limits<E>(low: f32, high: f32) -> Result<(RawLimit, RawLimit), Error<E>> {
+    match (lux_to_raw(low), lux_to_raw(high)) {
+        (Some(low), Some(high)) if raw_to_lux(low) < raw_to_lux(high) => Ok((low, high)),
+        _ => Err(Error::InvalidInputData),
+    }
+}
@@ -0,0 +1,194 @@
+//! Conversion timing and power model
+use crate::{Clock, ConversionTimes, IntegrationTime, LuxRange, MeasurementMode, PowerEstimate};
+
+/// Additional time allowance for the automatic full-scale range selection
+/// in microseconds
+const AUTO_RANGE_EXTRA_US: u32 = 10_000;
+/// Conversion time tolerance in percent
+const CONVERSION_TIME_TOLERANCE_PERCENT: u32 = 10;
+/// Typical active supply current in microamperes
+const ACTIVE_CURRENT_UA: f32 = 1.8;
+/// Typical shutdown supply current in microamperes
+const SHUTDOWN_CURRENT_UA: f32 = 0.3;
+
+impl<F> Clock for F
+where
+    F: FnMut() -> u64,
+{
+    fn now_us(&mut self) -> u64 {
+        self()
+    }
+}
+
+impl IntegrationTime {
+    /// Nominal integration time in microseconds.
+    pub fn as_us(self) -> u32 {
+        match self {
+            IntegrationTime::Ms100 => 100_000,
+            IntegrationTime::Ms800 => 800_000,
+        }
+    }
+}
+
+impl PowerEstimate {
+    /// Estimate the conversion timing and average supply current.
+    ///
+    /// In one-shot mode one conversion is assumed to be started every
+    /// `sampling_interval_us` microseconds and the device stays shut down
+    /// for the rest of the interval. An interval shorter than the conversion
+    /// time results in back-to-back conversions.
+    /// In continuous mode the device is always converting and the
+    /// sampling interval is ignored.
+    ///
+    /// The conversion times include a 10% tolerance of the internal oscillator
+    /// for the maximum value and an allowance for the automatic full-scale
+    /// range selection when using `LuxRange::Auto`.
+    /// The supply current is derived from the typical active and shutdown
+    /// currents. Please see the device datasheet for further details.
+    pub fn new(
+        integration_time: IntegrationTime,
+        lux_range: LuxRange,
+        mode: MeasurementMode,
+        sampling_interval_us: u32,
+    ) -> Self {
+        let mut conversion_time_us = integration_time.as_us();
+        if lux_range == LuxRange::Auto {
+            conversion_time_us += AUTO_RANGE_EXTRA_US;
+        }
+        let max_conversion_time_us =
+            conversion_time_us + conversion_time_us / 100 * CONVERSION_TIME_TOLERANCE_PERCENT;
+        let duty_cycle = match mode {
+            MeasurementMode::Continuous => 1.0,
+            MeasurementMode::OneShot if sampling_interval_us <= conversion_time_us => 1.0,
+            MeasurementMode::OneShot => conversion_time_us as f32 / sampling_interval_us as f32,
+        };
+        PowerEstimate {
+            conversion_time_us,
+            max_conversion_time_us,
+            average_current_ua: ACTIVE_CURRENT_UA * duty_cycle
+                + SHUTDOWN_CURRENT_UA * (1.0 - duty_cycle),
+        }
+    }
+}
+
+impl ConversionTimes {
+    /// A one-shot conversion was started
+    pub(crate) fn started(&mut self, now: u64) {
+        self.started_at = Some(now);
+        self.not_ready_at = Some(now);
+    }
+
+    /// The conversion was observed not to be ready
+    pub(crate) fn not_ready(&mut self, now: u64) {
+        self.not_ready_at = Some(now);
+    }
+
+    /// The conversion was observed to be ready.
+    ///
+    /// Returns the estimated conversion end time. The conversion ended after
+    /// the last time it was observed not to be ready and the expected end is
+    /// derived from the start of the conversion or from the end of the
+    /// previous one.
+    pub(crate) fn ready(&mut self, now: u64, period_us: u32) -> u64 {
+        let period = u64::from(period_us).max(1);
+        let expected = match (self.started_at, self.last_end) {
+            (Some(start), _) => Some(start + period),
+            (None, Some(last_end)) if now > last_end + period => {
+                Some(last_end + (now - last_end) / period * period)
+            }
+            (None, Some(last_end)) => Some(last_end + period),
+            (None, None) => None,
+        };
+        let end = match (expected, self.not_ready_at) {
+            (Some(expected), Some(lower)) => expected.max(lower),
+            (Some(expected), None) => expected,
+            (None, Some(lower)) => lower + (now - lower) / 2,
+            (None, None) => now,
+        }
+        .min(now);
+        self.started_at = None;
+        // Reading the status clears the conversion ready flag so the next
+        // conversion cannot end before now.
+        self.not_ready_at = Some(now);
+        self.last_end = Some(end);
+        end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_estimate_manual_range_timing() {
+        let estimate = PowerEstimate::new(
+            IntegrationTime::Ms800,
+            LuxRange::Manual(3),
+            MeasurementMode::Continuous,
+            0,
+        );
+        assert_eq!(800_000, estimate.conversion_time_us);
+        assert_eq!(880_000, estimate.max_conversion_time_us);
+        assert_eq!(ACTIVE_CURRENT_UA, estimate.average_current_ua);
+    }
+
+    #[test]
+    fn auto_range_takes_longer() {
+        let estimate = PowerEstimate::new(
+            IntegrationTime::Ms100,
+            LuxRange::Auto,
+            MeasurementMode::Continuous,
+            0,
+        );
+        assert_eq!(110_000, estimate.conversion_time_us);
+        assert_eq!(121_000, estimate.max_conversion_time_us);
+    }
+
+    #[test]
+    fn one_shot_current_depends_on_interval() {
+        let estimate = PowerEstimate::new(
+            IntegrationTime::Ms100,
+            LuxRange::Manual(0),
+            MeasurementMode::OneShot,
+            1_000_000,
+        );
+        let expected = ACTIVE_CURRENT_UA * 0.1 + SHUTDOWN_CURRENT_UA * 0.9;
+        assert!((expected - estimate.average_current_ua).abs() < 1e-6);
+    }
+
+    #[test]
+    fn one_shot_conversion_ends_one_period_after_start() {
+        let mut times = ConversionTimes::default();
+        times.started(1_000);
+        times.not_ready(500_000);
+        assert_eq!(801_000, times.ready(900_000, 800_000));
+    }
+
+    #[test]
+    fn conversion_end_is_not_later_than_observation() {
+        let mut times = ConversionTimes::default();
+        times.started(1_000);
+        assert_eq!(600_000, times.ready(600_000, 800_000));
+    }
+
+    #[test]
+    fn continuous_conversion_end_follows_previous_one() {
+        let mut times = ConversionTimes::default();
+        times.not_ready(100);
+        assert_eq!(100_050, times.ready(200_000, 800_000));
+        times.not_ready(850_000);
+        assert_eq!(900_050, times.ready(1_000_000, 800_000));
+        assert_eq!(2_500_050, times.ready(2_600_000, 800_000));
+    }
+
+    #[test]
+    fn short_one_shot_interval_means_always_active() {
+        let estimate = PowerEstimate::new(
+            IntegrationTime::Ms800,
+            LuxRange::Manual(0),
+            MeasurementMode::OneShot,
+            500_000,
+        );
+        assert_eq!(ACTIVE_CURRENT_UA, estimate.average_current_ua);
+    }
+}
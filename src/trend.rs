@@ -0,0 +1,154 @@
+//! Illuminance trend estimation
+use crate::{measurement::lsb, Measurement, Trend, TrendEstimator};
+
+/// Microseconds in a minute
+const US_PER_MINUTE: f32 = 60_000_000.0;
+
+impl<const N: usize> Default for TrendEstimator<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> TrendEstimator<N> {
+    /// Create a new empty instance.
+    pub fn new() -> Self {
+        TrendEstimator {
+            samples: [(0, 0.0); N],
+            next: 0,
+            len: 0,
+        }
+    }
+
+    /// Add a measurement in lux with its timestamp in microseconds.
+    ///
+    /// Timestamps must be monotonic. Once the window is full, the oldest
+    /// measurement is discarded.
+    /// Values below the lowest LSB (0.01 lux) are taken as 0.01 lux so that
+    /// darkness has a finite logarithm.
+    pub fn add(&mut self, lux: f32, timestamp: u64) {
+        if N == 0 {
+            return;
+        }
+        self.samples[self.next] = (timestamp, libm::log10f(lux.max(lsb(0))));
+        self.next = (self.next + 1) % N;
+        self.len = (self.len + 1).min(N);
+    }
+
+    /// Add a timestamped measurement.
+    ///
+    /// Measurements without timestamp are ignored and `false` is returned.
+    /// See [`read_measurement_timestamped()`](crate::Opt300x::read_measurement_timestamped).
+    pub fn add_measurement(&mut self, measurement: &Measurement<f32>) -> bool {
+        match measurement.timestamp {
+            Some(timestamp) => {
+                self.add(measurement.result, timestamp);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Number of measurements in the window.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the window contains no measurements.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Discard all measurements.
+    pub fn clear(&mut self) {
+        self.next = 0;
+        self.len = 0;
+    }
+
+    /// Estimate the trend with a least-squares line fit of `log10(lux)`
+    /// over time for the measurements in the window.
+    ///
+    /// Returns `None` if there are fewer than two measurements or if all
+    /// of them have the same timestamp.
+    pub fn trend(&self) -> Option<Trend> {
+        if self.len < 2 {
+            return None;
+        }
+        let newest = self.samples[(self.next + N - 1) % N].0;
+        // Times in minutes relative to the newest measurement
+        let time = |timestamp: u64| -(newest.saturating_sub(timestamp) as f32) / US_PER_MINUTE;
+        let samples = self.samples.iter().take(self.len);
+        let count = self.len as f32;
+        let (sum_t, sum_y) = samples.clone().fold((0.0, 0.0), |(t, y), sample| {
+            (t + time(sample.0), y + sample.1)
+        });
+        let (mean_t, mean_y) = (sum_t / count, sum_y / count);
+        let (sxy, sxx) = samples.fold((0.0, 0.0), |(sxy, sxx), sample| {
+            let dt = time(sample.0) - mean_t;
+            (sxy + dt * (sample.1 - mean_y), sxx + dt * dt)
+        });
+        if sxx == 0.0 {
+            return None;
+        }
+        let rate = sxy / sxx;
+        let log_lux = mean_y - rate * mean_t;
+        Some(Trend {
+            rate,
+            lux: libm::powf(10.0, log_lux),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(expected: f32, value: f32) {
+        assert!((expected - value).abs() < 1e-3, "{} != {}", expected, value);
+    }
+
+    #[test]
+    fn needs_two_measurements() {
+        let mut estimator = TrendEstimator::<4>::new();
+        assert_eq!(None, estimator.trend());
+        estimator.add(100.0, 0);
+        assert_eq!(None, estimator.trend());
+        estimator.add(100.0, 0);
+        assert_eq!(None, estimator.trend());
+    }
+
+    #[test]
+    fn can_estimate_exponential_change() {
+        let mut estimator = TrendEstimator::<8>::new();
+        // One decade per minute
+        for i in 0..5 {
+            let lux = libm::powf(10.0, 1.0 + i as f32 / 4.0);
+            estimator.add(lux, i * 15_000_000);
+        }
+        let trend = estimator.trend().unwrap();
+        assert_near(1.0, trend.rate);
+        assert!((trend.lux - 100.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn window_slides() {
+        let mut estimator = TrendEstimator::<3>::new();
+        estimator.add(1000.0, 0);
+        estimator.add(10.0, 60_000_000);
+        for i in 2..5 {
+            estimator.add(50.0, i * 60_000_000);
+        }
+        assert_eq!(3, estimator.len());
+        let trend = estimator.trend().unwrap();
+        assert_near(0.0, trend.rate);
+        assert!((trend.lux - 50.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn darkness_has_finite_trend() {
+        let mut estimator = TrendEstimator::<2>::new();
+        estimator.add(0.0, 0);
+        estimator.add(0.1, 60_000_000);
+        assert_near(1.0, estimator.trend().unwrap().rate);
+    }
+}
@@ -0,0 +1,138 @@
+//! Self-recentering threshold window implementation
+use crate::{
+    measurement::{full_scale, lsb, lux_to_raw, EXPONENT_MAX},
+    mode, Error, FaultCount, LuxChange, Opt300x, WakeOnChange,
+};
+use embedded_hal::i2c;
+
+impl WakeOnChange {
+    /// Create a new instance.
+    ///
+    /// A change is reported when the illuminance deviates by more than
+    /// `threshold_percent` percent from the last value for `fault_count`
+    /// consecutive conversions.
+    pub fn new(threshold_percent: f32, fault_count: FaultCount) -> Self {
+        WakeOnChange {
+            threshold_percent,
+            fault_count,
+            center: None,
+        }
+    }
+
+    /// Current window center in lux.
+    ///
+    /// Returns `None` before calling [`start()`](WakeOnChange::start).
+    pub fn center(&self) -> Option<f32> {
+        self.center
+    }
+
+    /// Read the current illuminance and program a window around it.
+    ///
+    /// This returns `nb::Error::WouldBlock` until a conversion performed
+    /// entirely with the current configuration is ready.
+    /// See [`wait_for_valid_conversion()`](Opt300x::wait_for_valid_conversion).
+    ///
+    /// This then sets the fault count and the latched window-style comparison
+    /// mode. The window is at least one LSB wide on each side of the current
+    /// value. Returns the current illuminance in lux.
+    ///
+    /// Returns `Error::InvalidInputData` if the threshold is not positive.
+    ///
+    /// Note that this disables the end-of-conversion mode.
+    pub fn start<I2C, IC>(
+        &mut self,
+        sensor: &mut Opt300x<I2C, IC, mode::Continuous>,
+    ) -> nb::Result<f32, Error<I2C::Error>>
+    where
+        I2C: i2c::I2c,
+    {
+        if self.threshold_percent.is_nan() || self.threshold_percent <= 0.0 {
+            return Err(nb::Error::Other(Error::InvalidInputData));
+        }
+        let lux = sensor.wait_for_valid_conversion()?.result;
+        sensor
+            .set_fault_count(self.fault_count)
+            .map_err(nb::Error::Other)?;
+        self.recenter(sensor, lux).map_err(nb::Error::Other)?;
+        Ok(lux)
+    }
+
+    /// Handle an interrupt.
+    ///
+    /// This reads the events, which releases the latched interrupt.
+    /// If the illuminance left the window, the window is re-centered on the
+    /// current value, the threshold events are acknowledged and the change
+    /// is returned.
+    /// Otherwise `None` is returned.
+    pub fn handle_interrupt<I2C, IC>(
+        &mut self,
+        sensor: &mut Opt300x<I2C, IC, mode::Continuous>,
+    ) -> Result<Option<LuxChange>, Error<I2C::Error>>
+    where
+        I2C: i2c::I2c,
+    {
+        let event = match sensor.read_threshold_event()? {
+            Some(event) => event,
+            None => return Ok(None),
+        };
+        let current = sensor.read_lux()?;
+        let previous = self.center.unwrap_or(current);
+        self.recenter(sensor, current)?;
+        sensor.acknowledge_threshold_events();
+        Ok(Some(LuxChange {
+            event,
+            previous,
+            current,
+        }))
+    }
+
+    fn recenter<I2C, IC>(
+        &mut self,
+        sensor: &mut Opt300x<I2C, IC, mode::Continuous>,
+        lux: f32,
+    ) -> Result<(), Error<I2C::Error>>
+    where
+        I2C: i2c::I2c,
+    {
+        let (low, high) = window(lux, self.threshold_percent);
+        sensor.set_window(low, high)?;
+        self.center = Some(lux);
+        Ok(())
+    }
+}
+
+/// Window limits in lux around a value
+fn window(lux: f32, threshold_percent: f32) -> (f32, f32) {
+    let exponent = lux_to_raw(lux).map_or(EXPONENT_MAX, |(exponent, _)| exponent);
+    let delta = (lux * threshold_percent / 100.0).max(lsb(exponent));
+    let low = (lux - delta).max(0.0);
+    let high = (lux + delta).min(full_scale(EXPONENT_MAX));
+    (low, high)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_is_relative_to_value() {
+        let (low, high) = window(1000.0, 10.0);
+        assert!((low - 900.0).abs() < 0.01);
+        assert!((high - 1100.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn window_is_at_least_one_lsb_wide() {
+        assert_eq!((0.0, 0.01), window(0.0, 10.0));
+        let (low, high) = window(1000.0, 0.001);
+        assert!((low - 999.68).abs() < 0.001);
+        assert!((high - 1000.32).abs() < 0.001);
+    }
+
+    #[test]
+    fn window_is_within_range() {
+        let (low, high) = window(83_000.0, 10.0);
+        assert!((low - 74_700.0).abs() < 0.1);
+        assert_eq!(full_scale(EXPONENT_MAX), high);
+    }
+}
@@ -0,0 +1,222 @@
+//! Light level zone tracking implementation
+use crate::{
+    measurement::{full_scale, EXPONENT_MAX},
+    mode,
+    thresholds::lux_limits,
+    Error, FaultCount, Opt300x, ZoneBoundary, ZoneTracker, ZoneTransition,
+};
+use embedded_hal::i2c;
+
+impl<'a> ZoneTracker<'a> {
+    /// Create a new instance.
+    ///
+    /// The `boundaries` must be sorted in ascending order. `N` boundaries
+    /// define `N + 1` zones, where zone 0 is below the first boundary and
+    /// zone `N` is above the last one.
+    /// A zone transition is reported after the result stays beyond a boundary
+    /// for `fault_count` consecutive conversions.
+    pub fn new(boundaries: &'a [ZoneBoundary], fault_count: FaultCount) -> Self {
+        ZoneTracker {
+            boundaries,
+            fault_count,
+            zone: None,
+        }
+    }
+
+    /// Current zone.
+    ///
+    /// Returns `None` before calling [`start()`](ZoneTracker::start).
+    pub fn zone(&self) -> Option<usize> {
+        self.zone
+    }
+
+    /// Read the current illuminance, determine the current zone and program
+    /// a window with its boundaries.
+    ///
+    /// This returns `nb::Error::WouldBlock` until a conversion performed
+    /// entirely with the current configuration is ready.
+    /// See [`wait_for_valid_conversion()`](Opt300x::wait_for_valid_conversion).
+    ///
+    /// This then sets the fault count and the latched window-style comparison
+    /// mode. Returns the current zone.
+    ///
+    /// Returns `Error::InvalidInputData` if the boundaries are not sorted in
+    /// ascending order, if any hysteresis is negative or greater than its
+    /// boundary, if any boundary is outside the measurement range or if the
+    /// limits of a zone are the same once rounded to the device resolution.
+    /// The device is not accessed in that case.
+    ///
+    /// Note that this disables the end-of-conversion mode.
+    pub fn start<I2C, IC>(
+        &mut self,
+        sensor: &mut Opt300x<I2C, IC, mode::Continuous>,
+    ) -> nb::Result<usize, Error<I2C::Error>>
+    where
+        I2C: i2c::I2c,
+    {
+        if !are_valid(self.boundaries) {
+            return Err(nb::Error::Other(Error::InvalidInputData));
+        }
+        let lux = sensor.wait_for_valid_conversion()?.result;
+        sensor
+            .set_fault_count(self.fault_count)
+            .map_err(nb::Error::Other)?;
+        let zone = self.next_zone(0, lux);
+        self.enter(sensor, zone).map_err(nb::Error::Other)?;
+        Ok(zone)
+    }
+
+    /// Handle an interrupt.
+    ///
+    /// This reads the events, which releases the latched interrupt.
+    /// If the result crossed a boundary, the window is programmed with the
+    /// boundaries of the new zone, the threshold events are acknowledged and
+    /// the transition is returned. Several boundaries may be crossed at once.
+    /// Otherwise `None` is returned.
+    pub fn handle_interrupt<I2C, IC>(
+        &mut self,
+        sensor: &mut Opt300x<I2C, IC, mode::Continuous>,
+    ) -> Result<Option<ZoneTransition>, Error<I2C::Error>>
+    where
+        I2C: i2c::I2c,
+    {
+        if sensor.read_threshold_event()?.is_none() {
+            return Ok(None);
+        }
+        let lux = sensor.read_lux()?;
+        let from = self.zone.unwrap_or(0);
+        let to = self.next_zone(from, lux);
+        self.enter(sensor, to)?;
+        sensor.acknowledge_threshold_events();
+        if from == to {
+            Ok(None)
+        } else {
+            Ok(Some(ZoneTransition { from, to, lux }))
+        }
+    }
+
+    fn next_zone(&self, mut zone: usize, lux: f32) -> usize {
+        let boundaries = self.boundaries;
+        while zone < boundaries.len() && lux > boundaries[zone].lux {
+            zone += 1;
+        }
+        while zone > 0 && lux < lower_limit(&boundaries[zone - 1]) {
+            zone -= 1;
+        }
+        zone
+    }
+
+    fn enter<I2C, IC>(
+        &mut self,
+        sensor: &mut Opt300x<I2C, IC, mode::Continuous>,
+        zone: usize,
+    ) -> Result<(), Error<I2C::Error>>
+    where
+        I2C: i2c::I2c,
+    {
+        let (low, high) = window(self.boundaries, zone);
+        sensor.set_window(low, high)?;
+        self.zone = Some(zone);
+        Ok(())
+    }
+}
+
+/// Illuminance below which the lower zone is entered
+fn lower_limit(boundary: &ZoneBoundary) -> f32 {
+    boundary.lux - boundary.hysteresis
+}
+
+/// Window limits in lux of a zone
+fn window(boundaries: &[ZoneBoundary], zone: usize) -> (f32, f32) {
+    let low = match zone {
+        0 => 0.0,
+        _ => lower_limit(&boundaries[zone - 1]),
+    };
+    let high = boundaries
+        .get(zone)
+        .map_or(full_scale(EXPONENT_MAX), |boundary| boundary.lux);
+    (low, high)
+}
+
+fn are_valid(boundaries: &[ZoneBoundary]) -> bool {
+    let are_sorted = boundaries.windows(2).all(|pair| pair[0].lux < pair[1].lux);
+    let are_windows_valid = (0..=boundaries.len()).all(|zone| {
+        let (low, high) = window(boundaries, zone);
+        lux_limits::<()>(low, high).is_ok()
+    });
+    are_sorted
+        && boundaries.iter().all(|boundary| {
+            boundary.lux > 0.0
+                && boundary.lux <= full_scale(EXPONENT_MAX)
+                && boundary.hysteresis >= 0.0
+                && lower_limit(boundary) >= 0.0
+        })
+        && are_windows_valid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOUNDARIES: [ZoneBoundary; 3] = [
+        ZoneBoundary {
+            lux: 10.0,
+            hysteresis: 2.0,
+        },
+        ZoneBoundary {
+            lux: 100.0,
+            hysteresis: 10.0,
+        },
+        ZoneBoundary {
+            lux: 1000.0,
+            hysteresis: 100.0,
+        },
+    ];
+
+    #[test]
+    fn can_find_zone() {
+        let tracker = ZoneTracker::new(&BOUNDARIES, FaultCount::One);
+        assert_eq!(0, tracker.next_zone(0, 5.0));
+        assert_eq!(1, tracker.next_zone(0, 50.0));
+        assert_eq!(3, tracker.next_zone(0, 5000.0));
+        assert_eq!(0, tracker.next_zone(3, 5.0));
+    }
+
+    #[test]
+    fn zone_changes_have_hysteresis() {
+        let tracker = ZoneTracker::new(&BOUNDARIES, FaultCount::One);
+        assert_eq!(2, tracker.next_zone(2, 95.0));
+        assert_eq!(1, tracker.next_zone(2, 89.0));
+        assert_eq!(1, tracker.next_zone(1, 95.0));
+    }
+
+    #[test]
+    fn can_validate_boundaries() {
+        assert!(are_valid(&BOUNDARIES));
+        assert!(are_valid(&[]));
+        let mut boundaries = BOUNDARIES;
+        boundaries.swap(0, 1);
+        assert!(!are_valid(&boundaries));
+        let boundaries = [ZoneBoundary {
+            lux: 10.0,
+            hysteresis: 20.0,
+        }];
+        assert!(!are_valid(&boundaries));
+        let boundaries = [ZoneBoundary {
+            lux: 10.0,
+            hysteresis: f32::NAN,
+        }];
+        assert!(!are_valid(&boundaries));
+        let boundaries = [
+            ZoneBoundary {
+                lux: 10.0,
+                hysteresis: 0.0,
+            },
+            ZoneBoundary {
+                lux: 10.004,
+                hysteresis: 0.0,
+            },
+        ];
+        assert!(!are_valid(&boundaries));
+    }
+}
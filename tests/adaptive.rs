@@ -0,0 +1,101 @@
+extern crate embedded_hal_mock as hal;
+#[macro_use]
+extern crate nb;
+extern crate opt300x;
+use hal::eh1::i2c::Transaction as I2cTrans;
+use opt300x::{AdaptiveIntegration, AdaptiveSettings, Error, IntegrationTime};
+
+mod common;
+use self::common::{
+    destroy, new_opt3001, read, write, BitFlags as BF, Register as Reg, CFG_DEFAULT,
+};
+
+const CFG: u16 = CFG_DEFAULT | BF::MODE0 | BF::MODE1;
+const CFG_FAST: u16 = CFG & !BF::CT;
+const CFG_LONG_MANUAL: u16 = (CFG & 0x0FFF) | 0x1000;
+
+const SETTINGS: AdaptiveSettings = AdaptiveSettings {
+    fast_activity: 0.2,
+    slow_activity: 0.05,
+    dim_lux: 100.0,
+    bright_lux: 200.0,
+    min_conversions: 2,
+    adjust_range: true,
+};
+
+fn conversion(config: u16, result: u16) -> [I2cTrans; 2] {
+    [
+        read(Reg::CONFIG, config | BF::CRF),
+        read(Reg::RESULT, result),
+    ]
+}
+
+#[test]
+fn switches_to_long_conversions_when_stable_and_dim() {
+    let mut transactions = vec![
+        write(Reg::CONFIG, CFG),
+        write(Reg::CONFIG, CFG),
+        write(Reg::CONFIG, CFG_FAST),
+    ];
+    transactions.extend_from_slice(&conversion(CFG_FAST, 0x0FFF));
+    transactions.extend_from_slice(&conversion(CFG_FAST, 0x0FA0));
+    transactions.extend_from_slice(&conversion(CFG_FAST, 0x0FA0));
+    transactions.push(write(Reg::CONFIG, (CFG_FAST & 0x0FFF) | 0x1000));
+    transactions.push(write(Reg::CONFIG, CFG_LONG_MANUAL));
+    let sensor = new_opt3001(&transactions);
+    let mut sensor = sensor.into_continuous().ok().unwrap();
+    let mut controller = AdaptiveIntegration::new(SETTINGS);
+    controller.start(&mut sensor).unwrap();
+    let measurement = block!(controller.poll(&mut sensor)).unwrap();
+    assert!(!measurement.stale);
+    assert_eq!(IntegrationTime::Ms100, measurement.info.integration_time);
+    let measurement = block!(controller.poll(&mut sensor)).unwrap();
+    assert!((measurement.result - 40.0).abs() < 0.01);
+    assert_eq!(0.0, controller.activity());
+    destroy(sensor);
+}
+
+#[test]
+fn switches_to_short_conversions_on_overflow_without_holding() {
+    let mut transactions = vec![
+        write(Reg::CONFIG, CFG),
+        write(Reg::CONFIG, CFG),
+        write(Reg::CONFIG, CFG_FAST),
+    ];
+    for _ in 0..3 {
+        transactions.extend_from_slice(&conversion(CFG_FAST, 0x0FA0));
+    }
+    transactions.push(write(Reg::CONFIG, (CFG_FAST & 0x0FFF) | 0x1000));
+    transactions.push(write(Reg::CONFIG, CFG_LONG_MANUAL));
+    transactions.extend_from_slice(&conversion(CFG_LONG_MANUAL, 0x0FA0));
+    transactions.extend_from_slice(&conversion(CFG_LONG_MANUAL | BF::OVF, 0x1FFF));
+    transactions.push(write(Reg::CONFIG, CFG));
+    transactions.push(write(Reg::CONFIG, CFG_FAST));
+    let sensor = new_opt3001(&transactions);
+    let mut sensor = sensor.into_continuous().ok().unwrap();
+    let mut controller = AdaptiveIntegration::new(SETTINGS);
+    controller.start(&mut sensor).unwrap();
+    block!(controller.poll(&mut sensor)).unwrap();
+    block!(controller.poll(&mut sensor)).unwrap();
+    let measurement = block!(controller.poll(&mut sensor)).unwrap();
+    assert!(measurement.status.has_overflown);
+    assert_eq!(IntegrationTime::Ms800, measurement.info.integration_time);
+    destroy(sensor);
+}
+
+#[test]
+fn cannot_start_with_invalid_settings() {
+    let sensor = new_opt3001(&[write(Reg::CONFIG, CFG)]);
+    let mut sensor = sensor.into_continuous().ok().unwrap();
+    let settings = AdaptiveSettings {
+        slow_activity: 0.3,
+        ..SETTINGS
+    };
+    assert_invalid_input_data!(AdaptiveIntegration::new(settings).start(&mut sensor));
+    let settings = AdaptiveSettings {
+        dim_lux: 300.0,
+        ..SETTINGS
+    };
+    assert_invalid_input_data!(AdaptiveIntegration::new(settings).start(&mut sensor));
+    destroy(sensor);
+}
@@ -39,6 +39,20 @@ pub fn destroy<IC, MODE>(sensor: Opt300x<I2cMock, IC, MODE>) {
     sensor.destroy().done();
 }
 
+#[allow(unused)]
+pub fn write(register: u8, value: u16) -> I2cTrans {
+    I2cTrans::write(DEV_ADDR, vec![register, (value >> 8) as u8, value as u8])
+}
+
+#[allow(unused)]
+pub fn read(register: u8, value: u16) -> I2cTrans {
+    I2cTrans::write_read(
+        DEV_ADDR,
+        vec![register],
+        vec![(value >> 8) as u8, value as u8],
+    )
+}
+
 #[macro_export]
 macro_rules! assert_invalid_input_data {
     ($result:expr) => {
@@ -0,0 +1,152 @@
+extern crate embedded_hal_mock as hal;
+#[macro_use]
+extern crate nb;
+extern crate opt300x;
+use opt300x::{ComparisonMode, Event, Events, IntegrationTime};
+
+mod common;
+use self::common::{
+    destroy, new_opt3001, read, write, BitFlags as BF, Register as Reg, CFG_DEFAULT,
+};
+
+#[test]
+fn can_read_latched_events() {
+    let transactions = [
+        read(Reg::CONFIG, CFG_DEFAULT | BF::FH | BF::OVF),
+        read(Reg::CONFIG, CFG_DEFAULT | BF::FL),
+    ];
+    let mut sensor = new_opt3001(&transactions);
+    let events = sensor.read_events().unwrap();
+    assert!(events.contains(Event::RoseAboveHigh));
+    assert!(events.contains(Event::Overflow));
+    assert!(!events.contains(Event::FellBelowLow));
+    sensor.acknowledge_events(events);
+    assert!(sensor.pending_events().is_empty());
+    let events = sensor.read_events().unwrap();
+    assert_eq!(Events::from(Event::FellBelowLow), events);
+    destroy(sensor);
+}
+
+#[test]
+fn events_stay_pending_until_acknowledged() {
+    let transactions = [
+        read(Reg::CONFIG, CFG_DEFAULT | BF::FH),
+        read(Reg::CONFIG, CFG_DEFAULT | BF::CRF),
+    ];
+    let mut sensor = new_opt3001(&transactions);
+    sensor.read_events().unwrap();
+    let events = sensor.read_events().unwrap();
+    assert!(events.contains(Event::RoseAboveHigh));
+    assert!(events.contains(Event::ConversionReady));
+    sensor.acknowledge_events(Event::ConversionReady.into());
+    assert_eq!(Events::from(Event::RoseAboveHigh), sensor.pending_events());
+    destroy(sensor);
+}
+
+#[test]
+fn transparent_mode_reports_transitions() {
+    let cfg = CFG_DEFAULT & !BF::L;
+    let transactions = [
+        write(Reg::CONFIG, cfg),
+        read(Reg::CONFIG, cfg | BF::FH),
+        read(Reg::CONFIG, cfg | BF::FH),
+        read(Reg::CONFIG, cfg),
+    ];
+    let mut sensor = new_opt3001(&transactions);
+    sensor
+        .set_comparison_mode(ComparisonMode::TransparentHysteresis)
+        .unwrap();
+    let events = sensor.read_events().unwrap();
+    assert_eq!(Events::from(Event::RoseAboveHigh), events);
+    sensor.acknowledge_events(events);
+    assert!(sensor.read_events().unwrap().is_empty());
+    assert_eq!(
+        Events::from(Event::FellBelowLow),
+        sensor.read_events().unwrap()
+    );
+    destroy(sensor);
+}
+
+#[test]
+fn conversion_ready_is_kept_for_measurement() {
+    let cfg = CFG_DEFAULT | BF::MODE0 | BF::MODE1;
+    let transactions = [
+        write(Reg::CONFIG, cfg),
+        read(Reg::CONFIG, cfg | BF::CRF),
+        read(Reg::CONFIG, cfg),
+        read(Reg::RESULT, 0x3456),
+    ];
+    let sensor = new_opt3001(&transactions);
+    let mut sensor = sensor.into_continuous().ok().unwrap();
+    let events = sensor.read_events().unwrap();
+    assert!(events.contains(Event::ConversionReady));
+    let measurement = block!(sensor.read_measurement()).unwrap();
+    assert!(measurement.status.conversion_ready);
+    destroy(sensor);
+}
+
+#[test]
+fn conversion_ready_is_discarded_when_restarting_conversion() {
+    let transactions = [
+        write(Reg::CONFIG, CFG_DEFAULT | BF::MODE0),
+        read(Reg::CONFIG, CFG_DEFAULT | BF::CRF),
+        write(Reg::CONFIG, CFG_DEFAULT | BF::MODE0),
+        read(Reg::CONFIG, CFG_DEFAULT | BF::MODE0),
+    ];
+    let mut sensor = new_opt3001(&transactions);
+    sensor.start_conversion().unwrap();
+    assert!(sensor
+        .read_events()
+        .unwrap()
+        .contains(Event::ConversionReady));
+    let token = sensor.start_conversion().unwrap();
+    assert_eq!(None, sensor.poll(&token).unwrap());
+    destroy(sensor);
+}
+
+#[test]
+fn conversion_ready_is_discarded_when_config_change_restarts_conversion() {
+    let cfg = CFG_DEFAULT & !BF::CT;
+    let transactions = [
+        write(Reg::CONFIG, CFG_DEFAULT | BF::MODE0),
+        read(Reg::CONFIG, CFG_DEFAULT | BF::CRF),
+        write(Reg::CONFIG, cfg | BF::MODE0),
+        read(Reg::CONFIG, cfg | BF::MODE0),
+    ];
+    let mut sensor = new_opt3001(&transactions);
+    let token = sensor.start_conversion().unwrap();
+    assert!(sensor
+        .read_events()
+        .unwrap()
+        .contains(Event::ConversionReady));
+    sensor.set_integration_time(IntegrationTime::Ms100).unwrap();
+    assert_eq!(None, sensor.poll(&token).unwrap());
+    destroy(sensor);
+}
+
+#[test]
+fn conversion_ready_is_discarded_when_config_changes_in_continuous_mode() {
+    let cfg = CFG_DEFAULT | BF::MODE0 | BF::MODE1;
+    let cfg_fast = cfg & !BF::CT;
+    let transactions = [
+        write(Reg::CONFIG, cfg),
+        read(Reg::CONFIG, cfg | BF::CRF),
+        write(Reg::CONFIG, cfg_fast),
+        read(Reg::CONFIG, cfg_fast),
+        read(Reg::CONFIG, cfg_fast | BF::CRF),
+        read(Reg::RESULT, 0x1234),
+        read(Reg::CONFIG, cfg_fast | BF::CRF),
+        read(Reg::RESULT, 0x3456),
+    ];
+    let sensor = new_opt3001(&transactions);
+    let mut sensor = sensor.into_continuous().ok().unwrap();
+    assert!(sensor
+        .read_events()
+        .unwrap()
+        .contains(Event::ConversionReady));
+    sensor.set_integration_time(IntegrationTime::Ms100).unwrap();
+    let measurement = block!(sensor.wait_for_valid_conversion()).unwrap();
+    assert!(!measurement.stale);
+    assert!((measurement.result - 88.8).abs() < 0.01);
+    destroy(sensor);
+}
@@ -0,0 +1,144 @@
+extern crate embedded_hal_mock as hal;
+extern crate opt300x;
+use hal::eh1::i2c::Transaction as I2cTrans;
+use opt300x::{
+    ComparisonMode, ConfigChange, FaultCount, InFlightConfigPolicy, IntegrationTime,
+    InterruptPinPolarity, LuxRange,
+};
+
+mod common;
+use self::common::{destroy, new_opt3001, BitFlags as BF, Register as Reg, CFG_DEFAULT, DEV_ADDR};
+
+fn write_config(config: u16) -> I2cTrans {
+    I2cTrans::write(
+        DEV_ADDR,
+        vec![Reg::CONFIG, (config >> 8) as u8, config as u8],
+    )
+}
+
+fn conversion_ready() -> [I2cTrans; 2] {
+    [
+        I2cTrans::write_read(
+            DEV_ADDR,
+            vec![Reg::CONFIG],
+            vec![(CFG_DEFAULT >> 8) as u8, (CFG_DEFAULT | BF::CRF) as u8],
+        ),
+        I2cTrans::write_read(DEV_ADDR, vec![Reg::RESULT], vec![0x78, 0x9A]),
+    ]
+}
+
+macro_rules! in_flight_test {
+    ($restart:ident, $defer:ident, $method:ident, $expected:expr $(, $arg:expr)*) => {
+        #[test]
+        fn $restart() {
+            let [status, result] = conversion_ready();
+            let transactions = [
+                write_config(CFG_DEFAULT | BF::MODE0),
+                write_config($expected | BF::MODE0),
+                status,
+                result,
+            ];
+            let mut sensor = new_opt3001(&transactions);
+            assert!(sensor.read_lux().is_err());
+            sensor.$method($($arg),*).unwrap();
+            assert_eq!(Some(ConfigChange::ConversionRestarted), sensor.last_config_change());
+            let measurement = sensor.read_lux().unwrap();
+            assert!(!measurement.stale);
+            destroy(sensor);
+        }
+
+        #[test]
+        fn $defer() {
+            let [status, result] = conversion_ready();
+            let transactions = [
+                write_config(CFG_DEFAULT | BF::MODE0),
+                status,
+                result,
+                write_config($expected),
+                write_config($expected | BF::MODE0),
+            ];
+            let mut sensor = new_opt3001(&transactions);
+            sensor.set_in_flight_config_policy(InFlightConfigPolicy::Defer);
+            assert!(sensor.read_lux().is_err());
+            sensor.$method($($arg),*).unwrap();
+            assert_eq!(Some(ConfigChange::Deferred), sensor.last_config_change());
+            let measurement = sensor.read_lux().unwrap();
+            assert!(!measurement.stale);
+            assert!(sensor.read_lux().is_err());
+            destroy(sensor);
+        }
+    };
+}
+
+in_flight_test!(
+    restart_fault_count,
+    defer_fault_count,
+    set_fault_count,
+    CFG_DEFAULT | 1,
+    FaultCount::Two
+);
+in_flight_test!(
+    restart_lux_range,
+    defer_lux_range,
+    set_lux_range,
+    CFG_DEFAULT & 0x0FFF | 1 << 12,
+    LuxRange::Manual(1)
+);
+in_flight_test!(
+    restart_integration_time,
+    defer_integration_time,
+    set_integration_time,
+    CFG_DEFAULT & !BF::CT,
+    IntegrationTime::Ms100
+);
+in_flight_test!(
+    restart_int_pin_polarity,
+    defer_int_pin_polarity,
+    set_interrupt_pin_polarity,
+    CFG_DEFAULT | BF::POL,
+    InterruptPinPolarity::High
+);
+in_flight_test!(
+    restart_enable_exponent_masking,
+    defer_enable_exponent_masking,
+    enable_exponent_masking,
+    CFG_DEFAULT | BF::ME
+);
+in_flight_test!(
+    restart_disable_exponent_masking,
+    defer_disable_exponent_masking,
+    disable_exponent_masking,
+    CFG_DEFAULT & !BF::ME
+);
+in_flight_test!(
+    restart_comparison_mode,
+    defer_comparison_mode,
+    set_comparison_mode,
+    CFG_DEFAULT & !BF::L,
+    ComparisonMode::TransparentHysteresis
+);
+
+#[test]
+fn config_changes_are_applied_when_idle() {
+    let transactions = [write_config(CFG_DEFAULT | 3)];
+    let mut sensor = new_opt3001(&transactions);
+    assert_eq!(None, sensor.last_config_change());
+    sensor.set_fault_count(FaultCount::Eight).unwrap();
+    assert_eq!(Some(ConfigChange::Applied), sensor.last_config_change());
+    destroy(sensor);
+}
+
+#[test]
+fn deferred_config_is_applied_when_changing_mode() {
+    let continuous = CFG_DEFAULT & !BF::CT | BF::MODE0 | BF::MODE1;
+    let transactions = [
+        write_config(CFG_DEFAULT | BF::MODE0),
+        write_config(continuous),
+    ];
+    let mut sensor = new_opt3001(&transactions);
+    sensor.set_in_flight_config_policy(InFlightConfigPolicy::Defer);
+    assert!(sensor.read_lux().is_err());
+    sensor.set_integration_time(IntegrationTime::Ms100).unwrap();
+    let sensor = sensor.into_continuous().ok().unwrap();
+    destroy(sensor);
+}
@@ -1,9 +1,14 @@
 extern crate embedded_hal_mock as hal;
+#[macro_use]
+extern crate nb;
 extern crate opt300x;
-use hal::eh1::i2c::{Mock as I2cMock, Transaction as I2cTrans};
+use hal::eh1::{
+    delay::NoopDelay,
+    i2c::{Mock as I2cMock, Transaction as I2cTrans},
+};
 use opt300x::{
-    ComparisonMode, Error, FaultCount, IntegrationTime, InterruptPinPolarity, LuxRange, Opt300x,
-    SlaveAddr, Status,
+    ic, mode, CalibrationError, ComparisonMode, Error, FaultCount, IntegrationTime,
+    InterruptPinPolarity, LuxRange, MeasurementInfo, Opt300x, SlaveAddr, Status,
 };
 
 mod common;
@@ -95,6 +100,44 @@ read_lux_test!(lux_5242_4, 0xB100, 5242.88);
 read_lux_test!(lux_20, 0xB001, 20.48);
 read_lux_test!(lux_83k, 0xBFFF, 83_865.6);
 
+#[test]
+fn can_read_measurement_in_continuous_mode() {
+    let value = 0x3456;
+    let cfg = CFG_DEFAULT | BF::MODE0 | BF::MODE1;
+    let transactions = [
+        I2cTrans::write(DEV_ADDR, vec![Reg::CONFIG, (cfg >> 8) as u8, cfg as u8]),
+        I2cTrans::write_read(
+            DEV_ADDR,
+            vec![Reg::CONFIG],
+            vec![(cfg >> 8) as u8, cfg as u8],
+        ),
+        I2cTrans::write_read(
+            DEV_ADDR,
+            vec![Reg::CONFIG],
+            vec![(cfg >> 8) as u8, (cfg | BF::CRF) as u8],
+        ),
+        I2cTrans::write_read(
+            DEV_ADDR,
+            vec![Reg::RESULT],
+            vec![(value >> 8) as u8, (value & 0xFF) as u8],
+        ),
+    ];
+    let sensor = new_opt3001(&transactions);
+    let mut sensor = sensor.into_continuous().ok().unwrap();
+    let measurement = block!(sensor.read_measurement()).unwrap();
+    assert!(measurement.result > 88.80 - 0.5);
+    assert!(measurement.result < 88.80 + 0.5);
+    assert!(measurement.status.conversion_ready);
+    assert_eq!(
+        MeasurementInfo {
+            exponent: 3,
+            integration_time: IntegrationTime::Ms800,
+        },
+        measurement.info
+    );
+    destroy(sensor);
+}
+
 macro_rules! read_raw_test {
     ($name:ident, $value:expr, $expected:expr) => {
         #[test]
@@ -425,3 +468,202 @@ fn configured_low_limit_is_restored_after_disabling_end_of_conv() {
     sensor.disable_end_of_conversion_mode().unwrap();
     destroy(sensor);
 }
+
+#[test]
+fn can_read_timestamped_measurement_in_continuous_mode() {
+    let cfg = CFG_DEFAULT | BF::MODE0 | BF::MODE1;
+    let status = |flags: u16| {
+        I2cTrans::write_read(
+            DEV_ADDR,
+            vec![Reg::CONFIG],
+            vec![((cfg | flags) >> 8) as u8, (cfg | flags) as u8],
+        )
+    };
+    let result = I2cTrans::write_read(DEV_ADDR, vec![Reg::RESULT], vec![0x34, 0x56]);
+    let transactions = [
+        I2cTrans::write(DEV_ADDR, vec![Reg::CONFIG, (cfg >> 8) as u8, cfg as u8]),
+        status(0),
+        status(BF::CRF),
+        result.clone(),
+        status(0),
+        status(BF::CRF),
+        result,
+    ];
+    let mut times = [100_u64, 200_000, 850_000, 1_000_000].iter().copied();
+    let mut clock = || times.next().unwrap();
+    let sensor = new_opt3001(&transactions);
+    let mut sensor = sensor.into_continuous().ok().unwrap();
+    let first = block!(sensor.read_measurement_timestamped(&mut clock)).unwrap();
+    assert_eq!(Some(100_050), first.timestamp);
+    let second = block!(sensor.read_measurement_timestamped(&mut clock)).unwrap();
+    assert_eq!(Some(910_050), second.timestamp);
+    destroy(sensor);
+}
+
+#[test]
+fn conversions_across_config_changes_are_discarded() {
+    let cfg = CFG_DEFAULT | BF::MODE0 | BF::MODE1;
+    let status = |cfg: u16, flags: u16| {
+        I2cTrans::write_read(
+            DEV_ADDR,
+            vec![Reg::CONFIG],
+            vec![((cfg | flags) >> 8) as u8, (cfg | flags) as u8],
+        )
+    };
+    let result = I2cTrans::write_read(DEV_ADDR, vec![Reg::RESULT], vec![0x34, 0x56]);
+    let cfg_100 = cfg & !BF::CT;
+    let transactions = [
+        I2cTrans::write(DEV_ADDR, vec![Reg::CONFIG, (cfg >> 8) as u8, cfg as u8]),
+        status(cfg, BF::CRF),
+        result.clone(),
+        status(cfg, BF::CRF),
+        result.clone(),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Reg::CONFIG, (cfg_100 >> 8) as u8, cfg_100 as u8],
+        ),
+        status(cfg_100, BF::CRF),
+        result.clone(),
+        status(cfg_100, 0),
+        status(cfg_100, BF::CRF),
+        result,
+    ];
+    let sensor = new_opt3001(&transactions);
+    let mut sensor = sensor.into_continuous().ok().unwrap();
+    assert!(block!(sensor.read_measurement()).unwrap().stale);
+    assert!(!block!(sensor.read_measurement()).unwrap().stale);
+    sensor.set_integration_time(IntegrationTime::Ms100).unwrap();
+    let measurement = block!(sensor.wait_for_valid_conversion()).unwrap();
+    assert!(!measurement.stale);
+    assert_eq!(IntegrationTime::Ms100, measurement.info.integration_time);
+    destroy(sensor);
+}
+
+fn calibration_status(flags: u16) -> I2cTrans {
+    let cfg = CFG_DEFAULT | BF::MODE0 | BF::MODE1 | flags;
+    I2cTrans::write_read(
+        DEV_ADDR,
+        vec![Reg::CONFIG],
+        vec![(cfg >> 8) as u8, cfg as u8],
+    )
+}
+
+fn new_continuous_opt3001(
+    transactions: &[I2cTrans],
+) -> Opt300x<I2cMock, ic::Opt3001, mode::Continuous> {
+    let cfg = CFG_DEFAULT | BF::MODE0 | BF::MODE1;
+    let mut all = vec![I2cTrans::write(
+        DEV_ADDR,
+        vec![Reg::CONFIG, (cfg >> 8) as u8, cfg as u8],
+    )];
+    all.extend_from_slice(transactions);
+    new_opt3001(&all).into_continuous().ok().unwrap()
+}
+
+#[test]
+fn can_calibrate_conversion_period() {
+    let cfg_100 = (CFG_DEFAULT | BF::MODE0 | BF::MODE1) & !BF::CT;
+    let transactions = [
+        calibration_status(0),
+        calibration_status(0),
+        calibration_status(BF::CRF),
+        calibration_status(0),
+        calibration_status(BF::CRF),
+        calibration_status(BF::CRF),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Reg::CONFIG, (cfg_100 >> 8) as u8, cfg_100 as u8],
+        ),
+    ];
+    let mut times = [0, 500, 1_000, 1_000, 1_500, 881_000, 881_000, 1_761_000]
+        .iter()
+        .copied();
+    let mut clock = || times.next().unwrap();
+    let mut sensor = new_continuous_opt3001(&transactions);
+    assert_eq!(810_000, sensor.conversion_period_us());
+    let period = sensor
+        .calibrate_conversion_period(&mut clock, &mut NoopDelay, 2)
+        .unwrap();
+    assert_eq!(880_000, period);
+    assert!((sensor.conversion_period_us() as i32 - 880_000).abs() <= 1);
+    sensor.set_integration_time(IntegrationTime::Ms100).unwrap();
+    assert!((sensor.conversion_period_us() as i32 - 119_506).abs() <= 1);
+    destroy(sensor);
+}
+
+#[test]
+fn calibration_discards_conversion_ready_flag_set_before_start() {
+    let transactions = [
+        calibration_status(BF::CRF),
+        calibration_status(0),
+        calibration_status(BF::CRF),
+        calibration_status(BF::CRF),
+    ];
+    let mut times = [0, 100_000, 700_000, 700_000, 1_580_000].iter().copied();
+    let mut clock = || times.next().unwrap();
+    let mut sensor = new_continuous_opt3001(&transactions);
+    let period = sensor
+        .calibrate_conversion_period(&mut clock, &mut NoopDelay, 1)
+        .unwrap();
+    assert_eq!(880_000, period);
+    destroy(sensor);
+}
+
+#[test]
+fn calibration_times_out() {
+    let transactions = [calibration_status(0), calibration_status(0)];
+    let mut times = [0, 10_000_000].iter().copied();
+    let mut clock = || times.next().unwrap();
+    let mut sensor = new_continuous_opt3001(&transactions);
+    match sensor.calibrate_conversion_period(&mut clock, &mut NoopDelay, 1) {
+        Err(CalibrationError::Timeout) => (),
+        _ => panic!("Timeout error not returned."),
+    }
+    destroy(sensor);
+}
+
+#[test]
+fn cannot_calibrate_zero_conversions() {
+    let mut sensor = new_continuous_opt3001(&[]);
+    match sensor.calibrate_conversion_period(&mut || 0, &mut NoopDelay, 0) {
+        Err(CalibrationError::InvalidInputData) => (),
+        _ => panic!("InvalidInputData error not returned."),
+    }
+    destroy(sensor);
+}
+
+#[test]
+fn can_change_low_limit_keeping_end_of_conv() {
+    let transactions = [
+        I2cTrans::write(DEV_ADDR, vec![Reg::LOW_LIMIT, 0b11 << 6, 0]),
+        I2cTrans::write(DEV_ADDR, vec![Reg::LOW_LIMIT, 0b1110_0001, 0x23]),
+        I2cTrans::write(DEV_ADDR, vec![Reg::LOW_LIMIT, 0x21, 0x23]),
+    ];
+    let mut sensor = new_opt3001(&transactions);
+    sensor.enable_end_of_conversion_mode().unwrap();
+    sensor
+        .set_low_limit_raw_keeping_end_of_conversion_mode(2, 0x123)
+        .unwrap();
+    assert!(sensor.is_end_of_conversion_mode_enabled());
+    assert_eq!((2, 0x123), sensor.low_limit_raw());
+    sensor.disable_end_of_conversion_mode().unwrap();
+    assert!(!sensor.is_end_of_conversion_mode_enabled());
+    destroy(sensor);
+}
+
+#[test]
+fn setting_low_limit_disables_end_of_conv() {
+    let transactions = [
+        I2cTrans::write(DEV_ADDR, vec![Reg::LOW_LIMIT, 0b11 << 6, 0]),
+        I2cTrans::write(DEV_ADDR, vec![Reg::LOW_LIMIT, 0x21, 0x23]),
+        I2cTrans::write(DEV_ADDR, vec![Reg::HIGH_LIMIT, 0x54, 0x56]),
+    ];
+    let mut sensor = new_opt3001(&transactions);
+    assert_eq!((0xB, 0xFFF), sensor.high_limit_raw());
+    sensor.enable_end_of_conversion_mode().unwrap();
+    sensor.set_low_limit_raw(2, 0x123).unwrap();
+    assert!(!sensor.is_end_of_conversion_mode_enabled());
+    sensor.set_high_limit_raw(5, 0x456).unwrap();
+    assert_eq!((5, 0x456), sensor.high_limit_raw());
+    destroy(sensor);
+}
@@ -3,7 +3,7 @@ extern crate embedded_hal_mock as hal;
 extern crate nb;
 extern crate opt300x;
 use hal::eh1::i2c::Transaction as I2cTrans;
-use opt300x::Status;
+use opt300x::{Error, IntegrationTime, MeasurementInfo, Status};
 
 mod common;
 use self::common::{destroy, new_opt3001, BitFlags as BF, Register as Reg, CFG_DEFAULT, DEV_ADDR};
@@ -53,5 +53,102 @@ fn read_measurement() {
         },
         measurement.status
     );
+    assert_eq!(
+        MeasurementInfo {
+            exponent: 7,
+            integration_time: IntegrationTime::Ms800,
+        },
+        measurement.info
+    );
+    assert_eq!(None, measurement.timestamp);
+    assert!(!measurement.stale);
+    destroy(sensor);
+}
+
+#[test]
+fn read_timestamped_measurement() {
+    let value = 0x789A;
+    let transactions = [
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![
+                Reg::CONFIG,
+                ((CFG_DEFAULT | BF::MODE0) >> 8) as u8,
+                CFG_DEFAULT as u8,
+            ],
+        ),
+        I2cTrans::write_read(
+            DEV_ADDR,
+            vec![Reg::CONFIG],
+            vec![((CFG_DEFAULT) >> 8) as u8, CFG_DEFAULT as u8],
+        ),
+        I2cTrans::write_read(
+            DEV_ADDR,
+            vec![Reg::CONFIG],
+            vec![(CFG_DEFAULT >> 8) as u8, (CFG_DEFAULT | BF::CRF) as u8],
+        ),
+        I2cTrans::write_read(
+            DEV_ADDR,
+            vec![Reg::RESULT],
+            vec![(value >> 8) as u8, (value & 0xFF) as u8],
+        ),
+    ];
+    let mut times = [1_000_u64, 500_000, 900_000].iter().copied();
+    let mut clock = || times.next().unwrap();
+    let mut sensor = new_opt3001(&transactions);
+    let measurement = block!(sensor.read_lux_timestamped(&mut clock)).unwrap();
+    assert_eq!(Some(811_000), measurement.timestamp);
+    destroy(sensor);
+}
+
+#[test]
+fn can_start_and_poll_conversion() {
+    let transactions = [
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![
+                Reg::CONFIG,
+                ((CFG_DEFAULT | BF::MODE0) >> 8) as u8,
+                CFG_DEFAULT as u8,
+            ],
+        ),
+        I2cTrans::write_read(
+            DEV_ADDR,
+            vec![Reg::CONFIG],
+            vec![((CFG_DEFAULT) >> 8) as u8, CFG_DEFAULT as u8],
+        ),
+        I2cTrans::write_read(
+            DEV_ADDR,
+            vec![Reg::CONFIG],
+            vec![(CFG_DEFAULT >> 8) as u8, (CFG_DEFAULT | BF::CRF) as u8],
+        ),
+        I2cTrans::write_read(DEV_ADDR, vec![Reg::RESULT], vec![0x78, 0x9A]),
+    ];
+    let mut sensor = new_opt3001(&transactions);
+    let token = sensor.start_conversion().unwrap();
+    assert_eq!(None, sensor.poll(&token).unwrap());
+    let measurement = sensor.poll(&token).unwrap().unwrap();
+    assert!(measurement.result > 2818.56 - 0.5);
+    assert!(measurement.result < 2818.56 + 0.5);
+    assert_invalid_input_data!(sensor.poll(&token));
+    destroy(sensor);
+}
+
+#[test]
+fn restarting_conversion_invalidates_token() {
+    let trigger = I2cTrans::write(
+        DEV_ADDR,
+        vec![
+            Reg::CONFIG,
+            ((CFG_DEFAULT | BF::MODE0) >> 8) as u8,
+            CFG_DEFAULT as u8,
+        ],
+    );
+    let transactions = [trigger.clone(), trigger];
+    let mut sensor = new_opt3001(&transactions);
+    let first = sensor.start_conversion().unwrap();
+    let second = sensor.start_conversion().unwrap();
+    assert_ne!(first, second);
+    assert_invalid_input_data!(sensor.poll(&first));
     destroy(sensor);
 }
@@ -0,0 +1,88 @@
+extern crate embedded_hal_mock as hal;
+#[macro_use]
+extern crate nb;
+extern crate opt300x;
+use hal::eh1::i2c::Transaction as I2cTrans;
+use opt300x::{Error, Oversampler};
+
+mod common;
+use self::common::{
+    destroy, new_opt3001, read, write, BitFlags as BF, Register as Reg, CFG_DEFAULT,
+};
+
+const CFG: u16 = CFG_DEFAULT | BF::MODE0 | BF::MODE1;
+const CFG_LOWEST_RANGE: u16 = CFG & 0x0FFF;
+
+fn conversion(config: u16, result: u16) -> [I2cTrans; 2] {
+    [
+        read(Reg::CONFIG, config | BF::CRF),
+        read(Reg::RESULT, result),
+    ]
+}
+
+fn start_transactions() -> Vec<I2cTrans> {
+    vec![
+        write(Reg::CONFIG, CFG),
+        write(Reg::CONFIG, CFG),
+        write(Reg::CONFIG, CFG_LOWEST_RANGE),
+    ]
+}
+
+#[test]
+fn averages_until_precise() {
+    let mut transactions = start_transactions();
+    transactions.extend_from_slice(&conversion(CFG_LOWEST_RANGE, 0x0FFF));
+    transactions.extend_from_slice(&conversion(CFG_LOWEST_RANGE, 0x0005));
+    transactions.push(read(Reg::CONFIG, CFG_LOWEST_RANGE));
+    transactions.extend_from_slice(&conversion(CFG_LOWEST_RANGE, 0x0005));
+    let sensor = new_opt3001(&transactions);
+    let mut sensor = sensor.into_continuous().ok().unwrap();
+    let mut oversampler = Oversampler::new(0.1, 16);
+    oversampler.start(&mut sensor).unwrap();
+    let result = block!(oversampler.poll(&mut sensor)).unwrap();
+    assert_eq!(2, result.samples);
+    assert!((result.mean - 0.05).abs() < 1e-6);
+    assert!(result.std_error < 0.005);
+    assert!(!result.has_overflown);
+    destroy(sensor);
+}
+
+#[test]
+fn stops_at_max_samples() {
+    let mut transactions = start_transactions();
+    transactions.extend_from_slice(&conversion(CFG_LOWEST_RANGE, 0x0005));
+    transactions.extend_from_slice(&conversion(CFG_LOWEST_RANGE, 0x0001));
+    transactions.extend_from_slice(&conversion(CFG_LOWEST_RANGE, 0x0009));
+    let sensor = new_opt3001(&transactions);
+    let mut sensor = sensor.into_continuous().ok().unwrap();
+    let mut oversampler = Oversampler::new(0.01, 2);
+    oversampler.start(&mut sensor).unwrap();
+    let result = block!(oversampler.poll(&mut sensor)).unwrap();
+    assert_eq!(2, result.samples);
+    assert!((result.mean - 0.05).abs() < 1e-6);
+    destroy(sensor);
+}
+
+#[test]
+fn stops_on_overflow() {
+    let mut transactions = start_transactions();
+    transactions.extend_from_slice(&conversion(CFG_LOWEST_RANGE, 0x0005));
+    transactions.extend_from_slice(&conversion(CFG_LOWEST_RANGE | BF::OVF, 0x0FFF));
+    let sensor = new_opt3001(&transactions);
+    let mut sensor = sensor.into_continuous().ok().unwrap();
+    let mut oversampler = Oversampler::new(0.01, 16);
+    oversampler.start(&mut sensor).unwrap();
+    let result = block!(oversampler.poll(&mut sensor)).unwrap();
+    assert_eq!(1, result.samples);
+    assert!(result.has_overflown);
+    destroy(sensor);
+}
+
+#[test]
+fn cannot_start_with_invalid_parameters() {
+    let sensor = new_opt3001(&[write(Reg::CONFIG, CFG)]);
+    let mut sensor = sensor.into_continuous().ok().unwrap();
+    assert_invalid_input_data!(Oversampler::new(0.0, 16).start(&mut sensor));
+    assert_invalid_input_data!(Oversampler::new(0.01, 1).start(&mut sensor));
+    destroy(sensor);
+}
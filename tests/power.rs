@@ -0,0 +1,94 @@
+extern crate embedded_hal;
+extern crate embedded_hal_mock as hal;
+extern crate opt300x;
+use embedded_hal::i2c::ErrorKind as I2cErrorKind;
+use hal::eh1::{
+    delay::NoopDelay,
+    pin::{Mock as PinMock, State as PinState, Transaction as PinTrans},
+    MockError,
+};
+use opt300x::{IntegrationTime, PowerError};
+use std::io::ErrorKind;
+
+mod common;
+use self::common::{
+    destroy, new_opt3001, read, write, BitFlags as BF, Register as Reg, CFG_DEFAULT,
+};
+
+#[test]
+fn can_power_down_and_up_restoring_config() {
+    let config = CFG_DEFAULT & !BF::CT;
+    let transactions = [
+        write(Reg::CONFIG, config),
+        write(Reg::HIGH_LIMIT, 0x1234),
+        write(Reg::LOW_LIMIT, 0),
+        write(Reg::HIGH_LIMIT, 0x1234),
+        write(Reg::CONFIG, config),
+        read(Reg::CONFIG, config),
+    ];
+    let pin = PinMock::new(&[PinTrans::set(PinState::Low), PinTrans::set(PinState::High)]);
+    let mut sensor = new_opt3001(&transactions).with_power_pin(pin);
+    sensor.set_integration_time(IntegrationTime::Ms100).unwrap();
+    sensor.set_high_limit_raw(1, 0x234).unwrap();
+    let sensor = sensor.power_down().ok().unwrap();
+    assert_eq!((1, 0x234), sensor.high_limit_raw());
+    let mut sensor = sensor.power_up(&mut NoopDelay).ok().unwrap();
+    sensor.read_status().unwrap();
+    let (sensor, mut pin) = sensor.destroy();
+    pin.done();
+    destroy(sensor);
+}
+
+#[test]
+fn continuous_mode_is_restored() {
+    let config = CFG_DEFAULT | BF::MODE0 | BF::MODE1;
+    let transactions = [
+        write(Reg::CONFIG, config),
+        write(Reg::LOW_LIMIT, 0),
+        write(Reg::HIGH_LIMIT, 0xBFFF),
+        write(Reg::CONFIG, config),
+    ];
+    let pin = PinMock::new(&[PinTrans::set(PinState::High)]);
+    let sensor = new_opt3001(&transactions)
+        .into_continuous()
+        .ok()
+        .unwrap()
+        .with_power_pin(pin);
+    let (sensor, pin) = sensor.destroy();
+    let sensor = opt300x::PoweredDown::new(sensor, pin);
+    let sensor = sensor.power_up(&mut NoopDelay).ok().unwrap();
+    let (sensor, mut pin) = sensor.destroy();
+    pin.done();
+    destroy(sensor);
+}
+
+#[test]
+fn pin_error_keeps_power_state() {
+    let error = MockError::Io(ErrorKind::NotConnected);
+    let pin = PinMock::new(&[PinTrans::set(PinState::Low).with_error(error.clone())]);
+    let sensor = new_opt3001(&[]).with_power_pin(pin);
+    match sensor.power_down() {
+        Err(PowerError::Pin(e, sensor)) => {
+            assert_eq!(error, e);
+            let (sensor, mut pin) = sensor.destroy();
+            pin.done();
+            destroy(sensor);
+        }
+        _ => panic!("Pin error not returned."),
+    }
+}
+
+#[test]
+fn i2c_error_powers_down_again() {
+    let transactions = [write(Reg::LOW_LIMIT, 0).with_error(I2cErrorKind::Other)];
+    let pin = PinMock::new(&[PinTrans::set(PinState::High), PinTrans::set(PinState::Low)]);
+    let sensor = opt300x::PoweredDown::new(new_opt3001(&transactions), pin);
+    match sensor.power_up(&mut NoopDelay) {
+        Err(PowerError::I2C(_, sensor)) => {
+            let (sensor, mut pin) = sensor.destroy();
+            pin.done();
+            destroy(sensor);
+        }
+        _ => panic!("I2C error not returned."),
+    }
+}
@@ -0,0 +1,90 @@
+extern crate embedded_hal;
+extern crate embedded_hal_mock as hal;
+#[macro_use]
+extern crate nb;
+extern crate opt300x;
+use embedded_hal::i2c::ErrorKind;
+use opt300x::{Error, MeasurementMode};
+
+mod common;
+use self::common::{
+    destroy, new_opt3001, read, write, BitFlags as BF, Register as Reg, CFG_DEFAULT,
+};
+
+const CFG_CONTINUOUS: u16 = CFG_DEFAULT | BF::MODE0 | BF::MODE1;
+
+#[test]
+fn can_change_into_runtime_mode_without_communication() {
+    let sensor = new_opt3001(&[]).into_runtime_mode();
+    assert_eq!(MeasurementMode::OneShot, sensor.measurement_mode());
+    destroy(sensor);
+}
+
+#[test]
+fn can_read_one_shot_measurement() {
+    let transactions = [
+        write(Reg::CONFIG, CFG_DEFAULT | BF::MODE0),
+        read(Reg::CONFIG, CFG_DEFAULT | BF::CRF),
+        read(Reg::RESULT, 0x3456),
+    ];
+    let mut sensor = new_opt3001(&transactions).into_runtime_mode();
+    let measurement = block!(sensor.read_measurement()).unwrap();
+    assert!((measurement.result - 88.8).abs() < 0.01);
+    destroy(sensor);
+}
+
+#[test]
+fn can_switch_mode_and_read_continuous_measurement() {
+    let transactions = [
+        write(Reg::CONFIG, CFG_CONTINUOUS),
+        read(Reg::CONFIG, CFG_CONTINUOUS),
+        read(Reg::CONFIG, CFG_CONTINUOUS | BF::CRF),
+        read(Reg::RESULT, 0x3456),
+        write(Reg::CONFIG, CFG_DEFAULT),
+    ];
+    let mut sensor = new_opt3001(&transactions).into_runtime_mode();
+    sensor.set_mode(MeasurementMode::Continuous).unwrap();
+    assert_eq!(MeasurementMode::Continuous, sensor.measurement_mode());
+    assert!(matches!(
+        sensor.read_measurement(),
+        Err(nb::Error::WouldBlock)
+    ));
+    let measurement = sensor.read_measurement().unwrap();
+    assert!((measurement.result - 88.8).abs() < 0.01);
+    sensor.set_mode(MeasurementMode::OneShot).unwrap();
+    assert_eq!(MeasurementMode::OneShot, sensor.measurement_mode());
+    destroy(sensor);
+}
+
+#[test]
+fn setting_current_mode_does_nothing() {
+    let mut sensor = new_opt3001(&[]).into_runtime_mode();
+    sensor.set_mode(MeasurementMode::OneShot).unwrap();
+    destroy(sensor);
+}
+
+#[test]
+fn mode_is_unchanged_on_error() {
+    let transactions = [write(Reg::CONFIG, CFG_CONTINUOUS).with_error(ErrorKind::Other)];
+    let mut sensor = new_opt3001(&transactions).into_runtime_mode();
+    match sensor.set_mode(MeasurementMode::Continuous) {
+        Err(Error::I2C(ErrorKind::Other)) => (),
+        _ => panic!("I2C error not returned."),
+    }
+    assert_eq!(MeasurementMode::OneShot, sensor.measurement_mode());
+    destroy(sensor);
+}
+
+#[test]
+fn can_change_into_typed_modes() {
+    let transactions = [
+        write(Reg::CONFIG, CFG_CONTINUOUS),
+        write(Reg::CONFIG, CFG_DEFAULT),
+    ];
+    let sensor = new_opt3001(&transactions).into_runtime_mode();
+    let sensor = sensor.into_continuous().ok().unwrap();
+    let sensor = sensor.into_runtime_mode();
+    assert_eq!(MeasurementMode::Continuous, sensor.measurement_mode());
+    let sensor = sensor.into_one_shot().ok().unwrap();
+    destroy(sensor);
+}
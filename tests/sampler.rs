@@ -0,0 +1,102 @@
+extern crate embedded_hal;
+extern crate embedded_hal_mock as hal;
+extern crate opt300x;
+use embedded_hal::i2c::ErrorKind;
+use hal::eh1::{delay::NoopDelay, i2c::Transaction as I2cTrans};
+use opt300x::{Error, PeriodicSampler};
+use std::cell::Cell;
+
+mod common;
+use self::common::{
+    destroy, new_opt3001, read, write, BitFlags as BF, Register as Reg, CFG_DEFAULT,
+};
+
+const PERIOD: u64 = 30_000_000;
+
+fn cycle() -> [I2cTrans; 3] {
+    [
+        write(Reg::CONFIG, CFG_DEFAULT | BF::MODE0),
+        read(Reg::CONFIG, CFG_DEFAULT | BF::CRF),
+        read(Reg::RESULT, 0x3456),
+    ]
+}
+
+#[test]
+fn samples_periodically() {
+    let mut transactions = Vec::new();
+    for _ in 0..3 {
+        transactions.extend_from_slice(&cycle());
+    }
+    let mut sensor = new_opt3001(&transactions);
+    let time = Cell::new(0);
+    let mut clock = || time.get();
+    let mut sampler = PeriodicSampler::new(PERIOD);
+    let sample = sampler
+        .sample(&mut sensor, &mut NoopDelay, &mut clock)
+        .unwrap();
+    assert_eq!(0, sample.deadline);
+    assert_eq!(0, sample.missed_deadlines);
+    let measurement = sample.measurement.unwrap();
+    assert!((measurement.result - 88.8).abs() < 0.01);
+    assert_eq!(Some(PERIOD), sampler.next_deadline());
+
+    time.set(10_000_000);
+    let sample = sampler
+        .sample(&mut sensor, &mut NoopDelay, &mut clock)
+        .unwrap();
+    assert_eq!(PERIOD, sample.deadline);
+    assert_eq!(0, sample.missed_deadlines);
+
+    time.set(100_000_000);
+    let sample = sampler
+        .sample(&mut sensor, &mut NoopDelay, &mut clock)
+        .unwrap();
+    assert_eq!(3 * PERIOD, sample.deadline);
+    assert_eq!(1, sample.missed_deadlines);
+    assert_eq!(1, sampler.missed_deadlines());
+    destroy(sensor);
+}
+
+#[test]
+fn shuts_down_on_timeout() {
+    let transactions = [
+        write(Reg::CONFIG, CFG_DEFAULT | BF::MODE0),
+        read(Reg::CONFIG, CFG_DEFAULT | BF::MODE0),
+        read(Reg::CONFIG, CFG_DEFAULT | BF::MODE0),
+        write(Reg::CONFIG, CFG_DEFAULT),
+    ];
+    let mut sensor = new_opt3001(&transactions);
+    let time = Cell::new(0);
+    let mut clock = || {
+        time.set(time.get() + 1_000_000);
+        time.get()
+    };
+    let mut sampler = PeriodicSampler::new(PERIOD);
+    let sample = sampler
+        .sample(&mut sensor, &mut NoopDelay, &mut clock)
+        .unwrap();
+    assert_eq!(None, sample.measurement);
+    destroy(sensor);
+}
+
+#[test]
+fn shuts_down_on_bus_error() {
+    let mut transactions = vec![
+        write(Reg::CONFIG, CFG_DEFAULT | BF::MODE0),
+        read(Reg::CONFIG, CFG_DEFAULT).with_error(ErrorKind::Other),
+        write(Reg::CONFIG, CFG_DEFAULT),
+    ];
+    transactions.extend_from_slice(&cycle());
+    let mut sensor = new_opt3001(&transactions);
+    let mut clock = || 0;
+    let mut sampler = PeriodicSampler::new(PERIOD);
+    match sampler.sample(&mut sensor, &mut NoopDelay, &mut clock) {
+        Err(Error::I2C(ErrorKind::Other)) => (),
+        _ => panic!("Bus error not reported."),
+    }
+    let sample = sampler
+        .sample(&mut sensor, &mut NoopDelay, &mut clock)
+        .unwrap();
+    assert!(sample.measurement.is_some());
+    destroy(sensor);
+}
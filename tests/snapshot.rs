@@ -0,0 +1,114 @@
+extern crate embedded_hal_mock as hal;
+extern crate opt300x;
+use hal::eh1::i2c::Transaction as I2cTrans;
+use opt300x::{ConfigSnapshot, RestoreError};
+
+mod common;
+use self::common::{
+    destroy, new_opt3001, read, write, BitFlags as BF, Register as Reg, CFG_DEFAULT,
+};
+
+const CFG_CONTINUOUS: u16 = CFG_DEFAULT | BF::MODE0 | BF::MODE1;
+const CFG_SNAPSHOT: u16 = CFG_DEFAULT & !BF::CT;
+
+fn read_snapshot(config: u16, low_limit: u16, high_limit: u16) -> [I2cTrans; 3] {
+    [
+        read(Reg::CONFIG, config),
+        read(Reg::LOW_LIMIT, low_limit),
+        read(Reg::HIGH_LIMIT, high_limit),
+    ]
+}
+
+fn snapshot(config: u16, low_limit: u16, high_limit: u16) -> ConfigSnapshot {
+    let mut sensor = new_opt3001(&read_snapshot(config, low_limit, high_limit));
+    let snapshot = sensor.snapshot().unwrap();
+    destroy(sensor);
+    snapshot
+}
+
+#[test]
+fn can_take_snapshot_and_convert_to_bytes() {
+    let snapshot = snapshot(CFG_SNAPSHOT | BF::OVF | BF::FH, 0xC000, 0x1234);
+    assert!(snapshot.is_end_of_conversion_mode_enabled());
+    let data = snapshot.to_bytes();
+    assert_eq!(ConfigSnapshot::SIZE, data.len());
+    assert_eq!([1, 0xC0, 0x10, 0xC0, 0x00, 0x12, 0x34], data[..7]);
+    assert_eq!(Some(snapshot), ConfigSnapshot::from_bytes(&data));
+}
+
+#[test]
+fn corrupted_bytes_are_rejected() {
+    let data = snapshot(CFG_SNAPSHOT, 0, 0xBFFF).to_bytes();
+    assert_eq!(None, ConfigSnapshot::from_bytes(&data[1..]));
+    let mut corrupted = data;
+    corrupted[3] ^= 1;
+    assert_eq!(None, ConfigSnapshot::from_bytes(&corrupted));
+    let mut other_version = data;
+    other_version[0] = 2;
+    assert_eq!(None, ConfigSnapshot::from_bytes(&other_version));
+}
+
+#[test]
+fn conversion_ready_flag_is_kept() {
+    let mut transactions = vec![write(Reg::CONFIG, CFG_CONTINUOUS)];
+    transactions.extend_from_slice(&read_snapshot(CFG_CONTINUOUS | BF::CRF, 0, 0xBFFF));
+    transactions.push(read(Reg::CONFIG, CFG_CONTINUOUS));
+    transactions.push(read(Reg::RESULT, 0x3456));
+    let mut sensor = new_opt3001(&transactions).into_continuous().ok().unwrap();
+    sensor.snapshot().unwrap();
+    let measurement = sensor.read_measurement().unwrap();
+    assert!((measurement.result - 88.8).abs() < 0.01);
+    destroy(sensor);
+}
+
+#[test]
+fn can_restore_snapshot() {
+    let snapshot = snapshot(CFG_SNAPSHOT, 0xC000, 0x1234);
+    let mut transactions = vec![
+        write(Reg::LOW_LIMIT, 0xC000),
+        write(Reg::HIGH_LIMIT, 0x1234),
+        write(Reg::CONFIG, CFG_SNAPSHOT),
+    ];
+    transactions.extend_from_slice(&read_snapshot(CFG_SNAPSHOT, 0xC000, 0x1234));
+    let mut sensor = new_opt3001(&transactions);
+    sensor.restore(&snapshot).unwrap();
+    assert!(sensor.is_end_of_conversion_mode_enabled());
+    assert_eq!((0, 0), sensor.low_limit_raw());
+    assert_eq!((1, 0x234), sensor.high_limit_raw());
+    destroy(sensor);
+}
+
+#[test]
+fn restoring_keeps_continuous_mode() {
+    let snapshot = snapshot(CFG_SNAPSHOT, 0x0123, 0x1234);
+    let mut transactions = vec![
+        write(Reg::CONFIG, CFG_CONTINUOUS),
+        write(Reg::LOW_LIMIT, 0x0123),
+        write(Reg::HIGH_LIMIT, 0x1234),
+        write(Reg::CONFIG, CFG_SNAPSHOT | BF::MODE0 | BF::MODE1),
+    ];
+    let read_back = CFG_SNAPSHOT | BF::MODE0 | BF::MODE1;
+    transactions.extend_from_slice(&read_snapshot(read_back, 0x0123, 0x1234));
+    let mut sensor = new_opt3001(&transactions).into_continuous().ok().unwrap();
+    sensor.restore(&snapshot).unwrap();
+    assert!(!sensor.is_end_of_conversion_mode_enabled());
+    assert_eq!((0, 0x123), sensor.low_limit_raw());
+    destroy(sensor);
+}
+
+#[test]
+fn restoring_detects_mismatch() {
+    let snapshot = snapshot(CFG_SNAPSHOT, 0, 0x1234);
+    let mut transactions = vec![
+        write(Reg::LOW_LIMIT, 0),
+        write(Reg::HIGH_LIMIT, 0x1234),
+        write(Reg::CONFIG, CFG_SNAPSHOT),
+    ];
+    transactions.extend_from_slice(&read_snapshot(CFG_SNAPSHOT, 0, 0xBFFF));
+    let mut sensor = new_opt3001(&transactions);
+    match sensor.restore(&snapshot) {
+        Err(RestoreError::VerificationFailed) => (),
+        _ => panic!("VerificationFailed error not returned."),
+    }
+    destroy(sensor);
+}
@@ -0,0 +1,109 @@
+extern crate embedded_hal_mock as hal;
+extern crate opt300x;
+use hal::eh1::i2c::Mock as I2cMock;
+use opt300x::{ic, mode, DriverState, IntegrationTime, Opt300x};
+use std::cell::Cell;
+
+mod common;
+use self::common::{
+    destroy, new_opt3001, read, write, BitFlags as BF, Register as Reg, CFG_DEFAULT,
+};
+
+#[test]
+fn can_rebuild_driver_without_communication() {
+    let config = CFG_DEFAULT & !BF::CT;
+    let transactions = [write(Reg::CONFIG, config), write(Reg::HIGH_LIMIT, 0x1234)];
+    let mut sensor = new_opt3001(&transactions);
+    sensor.set_integration_time(IntegrationTime::Ms100).unwrap();
+    sensor.set_high_limit_raw(1, 0x234).unwrap();
+    let (mut i2c, state) = sensor.into_parts();
+    i2c.done();
+
+    let transactions = [write(Reg::CONFIG, config | BF::MODE0)];
+    let mut sensor = Opt300x::from_parts(I2cMock::new(&transactions), state);
+    assert_eq!((1, 0x234), sensor.high_limit_raw());
+    assert!(sensor.read_lux().is_err());
+    destroy(sensor);
+}
+
+#[test]
+fn conversion_in_progress_is_kept() {
+    let transactions = [write(Reg::CONFIG, CFG_DEFAULT | BF::MODE0)];
+    let mut sensor = new_opt3001(&transactions);
+    assert!(sensor.read_lux().is_err());
+    let (mut i2c, state) = sensor.into_parts();
+    i2c.done();
+
+    let transactions = [
+        read(Reg::CONFIG, CFG_DEFAULT | BF::CRF),
+        read(Reg::RESULT, 0x3456),
+    ];
+    let mut sensor = Opt300x::from_parts(I2cMock::new(&transactions), state);
+    let measurement = sensor.read_lux().unwrap();
+    assert!((measurement.result - 88.8).abs() < 0.01);
+    destroy(sensor);
+}
+
+#[test]
+fn can_convert_state_to_bytes_and_back() {
+    let transactions = [
+        write(Reg::LOW_LIMIT, 0xC000),
+        write(Reg::CONFIG, CFG_DEFAULT | BF::MODE0),
+        read(Reg::CONFIG, CFG_DEFAULT),
+    ];
+    let mut sensor = new_opt3001(&transactions);
+    let time = Cell::new(1_000);
+    let mut clock = || time.get();
+    sensor.enable_end_of_conversion_mode().unwrap();
+    assert!(sensor.read_lux_timestamped(&mut clock).is_err());
+    time.set(2_000);
+    assert!(sensor.read_lux_timestamped(&mut clock).is_err());
+    let (mut i2c, state) = sensor.into_parts();
+    i2c.done();
+
+    let data = state.to_bytes();
+    assert_eq!(DriverState::<ic::Opt3001, mode::OneShot>::SIZE, data.len());
+    assert_eq!(Some(state), DriverState::from_bytes(&data));
+    let sensor = Opt300x::from_parts(I2cMock::new(&[]), state);
+    assert!(sensor.is_end_of_conversion_mode_enabled());
+    destroy(sensor);
+}
+
+#[test]
+fn invalid_bytes_are_rejected() {
+    let sensor = new_opt3001(&[]);
+    let (mut i2c, state) = sensor.into_parts();
+    i2c.done();
+    let data = state.to_bytes();
+    assert_eq!(
+        None,
+        DriverState::<ic::Opt3001, mode::OneShot>::from_bytes(&data[1..])
+    );
+    let mut other_version = data;
+    other_version[0] = 0;
+    assert_eq!(
+        None,
+        DriverState::<ic::Opt3001, mode::OneShot>::from_bytes(&other_version)
+    );
+    let mut corrupted = data;
+    corrupted[47] ^= 0x80;
+    assert_eq!(
+        None,
+        DriverState::<ic::Opt3001, mode::OneShot>::from_bytes(&corrupted)
+    );
+}
+
+#[test]
+fn mode_must_match() {
+    let transactions = [write(Reg::CONFIG, CFG_DEFAULT | BF::MODE0 | BF::MODE1)];
+    let sensor = new_opt3001(&transactions).into_continuous().ok().unwrap();
+    let (mut i2c, state) = sensor.into_parts();
+    i2c.done();
+    let data = state.to_bytes();
+    assert_eq!(
+        None,
+        DriverState::<ic::Opt3001, mode::OneShot>::from_bytes(&data)
+    );
+    assert!(DriverState::<ic::Opt3001, mode::Continuous>::from_bytes(&data).is_some());
+    assert!(DriverState::<ic::Opt3001, mode::Runtime>::from_bytes(&data).is_some());
+}
@@ -0,0 +1,80 @@
+extern crate embedded_hal_mock as hal;
+extern crate opt300x;
+use opt300x::Error;
+
+mod common;
+use self::common::{destroy, new_opt3001, write, BitFlags as BF, Register as Reg, CFG_DEFAULT};
+
+#[test]
+fn can_set_window() {
+    let transactions = [
+        write(Reg::LOW_LIMIT, 0x03E8),
+        write(Reg::HIGH_LIMIT, 0x29C4),
+        write(Reg::CONFIG, CFG_DEFAULT),
+    ];
+    let mut sensor = new_opt3001(&transactions);
+    sensor.set_window(10.0, 100.0).unwrap();
+    assert_eq!((0, 1000), sensor.low_limit_raw());
+    assert_eq!((2, 2500), sensor.high_limit_raw());
+    destroy(sensor);
+}
+
+#[test]
+fn moving_window_up_writes_high_limit_first() {
+    let transactions = [
+        write(Reg::LOW_LIMIT, 0x03E8),
+        write(Reg::HIGH_LIMIT, 0x29C4),
+        write(Reg::CONFIG, CFG_DEFAULT),
+        write(Reg::HIGH_LIMIT, 0x6C35),
+        write(Reg::LOW_LIMIT, 0x5C35),
+        write(Reg::CONFIG, CFG_DEFAULT),
+    ];
+    let mut sensor = new_opt3001(&transactions);
+    sensor.set_window(10.0, 100.0).unwrap();
+    sensor.set_window(1000.0, 2000.0).unwrap();
+    destroy(sensor);
+}
+
+#[test]
+fn can_set_hysteresis() {
+    let transactions = [
+        write(Reg::LOW_LIMIT, 0x49C4),
+        write(Reg::HIGH_LIMIT, 0x4C35),
+        write(Reg::CONFIG, CFG_DEFAULT & !BF::L),
+    ];
+    let mut sensor = new_opt3001(&transactions);
+    sensor.set_hysteresis(500.0, 100.0).unwrap();
+    destroy(sensor);
+}
+
+#[test]
+fn can_disable_thresholds_keeping_end_of_conv() {
+    let transactions = [
+        write(Reg::LOW_LIMIT, 0b11 << 14),
+        write(Reg::HIGH_LIMIT, 0xBFFF),
+        write(Reg::LOW_LIMIT, 0b11 << 14),
+    ];
+    let mut sensor = new_opt3001(&transactions);
+    sensor.enable_end_of_conversion_mode().unwrap();
+    sensor.disable_thresholds().unwrap();
+    assert!(sensor.is_end_of_conversion_mode_enabled());
+    destroy(sensor);
+}
+
+macro_rules! invalid_test {
+    ($name:ident, $method:ident $(, $arg:expr)*) => {
+        #[test]
+        fn $name() {
+            let mut sensor = new_opt3001(&[]);
+            assert_invalid_input_data!(sensor.$method($($arg),*));
+            destroy(sensor);
+        }
+    };
+}
+
+invalid_test!(inverted_window, set_window, 100.0, 10.0);
+invalid_test!(empty_window, set_window, 10.0, 10.0);
+invalid_test!(negative_window, set_window, -1.0, 10.0);
+invalid_test!(too_high_window, set_window, 10.0, 90_000.0);
+invalid_test!(zero_hysteresis_band, set_hysteresis, 10.0, 0.0);
+invalid_test!(too_big_hysteresis_band, set_hysteresis, 10.0, 20.0);
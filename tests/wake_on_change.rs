@@ -0,0 +1,77 @@
+extern crate embedded_hal_mock as hal;
+#[macro_use]
+extern crate nb;
+extern crate opt300x;
+use hal::eh1::i2c::Transaction as I2cTrans;
+use opt300x::{Error, Event, Events, FaultCount, WakeOnChange};
+
+mod common;
+use self::common::{
+    destroy, new_opt3001, read, write, BitFlags as BF, Register as Reg, CFG_DEFAULT,
+};
+
+const CFG: u16 = CFG_DEFAULT | BF::MODE0 | BF::MODE1 | 0b10;
+
+fn start_transactions() -> Vec<I2cTrans> {
+    vec![
+        write(Reg::CONFIG, CFG & !0b10),
+        read(Reg::CONFIG, (CFG & !0b10) | BF::CRF),
+        read(Reg::RESULT, 0),
+        read(Reg::CONFIG, CFG & !0b10),
+        read(Reg::CONFIG, (CFG & !0b10) | BF::CRF),
+        read(Reg::RESULT, 0x789A),
+        write(Reg::CONFIG, CFG),
+        write(Reg::LOW_LIMIT, 0x6F7C),
+        write(Reg::HIGH_LIMIT, 0x7976),
+        write(Reg::CONFIG, CFG),
+    ]
+}
+
+#[test]
+fn can_start() {
+    let sensor = new_opt3001(&start_transactions());
+    let mut sensor = sensor.into_continuous().ok().unwrap();
+    let mut wake = WakeOnChange::new(10.0, FaultCount::Four);
+    assert_eq!(None, wake.center());
+    assert!(wake.start(&mut sensor).is_err());
+    let lux = block!(wake.start(&mut sensor)).unwrap();
+    assert!((lux - 2818.56).abs() < 0.01);
+    assert_eq!(Some(lux), wake.center());
+    destroy(sensor);
+}
+
+#[test]
+fn recenters_window_on_change() {
+    let mut transactions = start_transactions();
+    transactions.extend_from_slice(&[
+        read(Reg::CONFIG, CFG | BF::FH),
+        read(Reg::RESULT, 0x7D00),
+        write(Reg::HIGH_LIMIT, 0x7E4D),
+        write(Reg::LOW_LIMIT, 0x7BB3),
+        write(Reg::CONFIG, CFG),
+        read(Reg::CONFIG, CFG),
+    ]);
+    let sensor = new_opt3001(&transactions);
+    let mut sensor = sensor.into_continuous().ok().unwrap();
+    let mut wake = WakeOnChange::new(10.0, FaultCount::Four);
+    block!(wake.start(&mut sensor)).unwrap();
+    let change = wake.handle_interrupt(&mut sensor).unwrap().unwrap();
+    assert_eq!(Event::RoseAboveHigh, change.event);
+    assert!((change.previous - 2818.56).abs() < 0.01);
+    assert!((change.current - 4259.84).abs() < 0.01);
+    assert_eq!(
+        Events::from(Event::ConversionReady),
+        sensor.pending_events()
+    );
+    assert_eq!(None, wake.handle_interrupt(&mut sensor).unwrap());
+    destroy(sensor);
+}
+
+#[test]
+fn cannot_start_with_invalid_threshold() {
+    let sensor = new_opt3001(&[write(Reg::CONFIG, CFG & !0b10)]);
+    let mut sensor = sensor.into_continuous().ok().unwrap();
+    let mut wake = WakeOnChange::new(0.0, FaultCount::Four);
+    assert_invalid_input_data!(block!(wake.start(&mut sensor)));
+    destroy(sensor);
+}
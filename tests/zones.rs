@@ -0,0 +1,113 @@
+extern crate embedded_hal_mock as hal;
+#[macro_use]
+extern crate nb;
+extern crate opt300x;
+use hal::eh1::i2c::Transaction as I2cTrans;
+use opt300x::{Error, Event, Events, FaultCount, ZoneBoundary, ZoneTracker};
+
+mod common;
+use self::common::{
+    destroy, new_opt3001, read, write, BitFlags as BF, Register as Reg, CFG_DEFAULT,
+};
+
+const CFG: u16 = CFG_DEFAULT | BF::MODE0 | BF::MODE1 | 0b01;
+
+const BOUNDARIES: [ZoneBoundary; 3] = [
+    ZoneBoundary {
+        lux: 10.0,
+        hysteresis: 2.0,
+    },
+    ZoneBoundary {
+        lux: 100.0,
+        hysteresis: 10.0,
+    },
+    ZoneBoundary {
+        lux: 1000.0,
+        hysteresis: 100.0,
+    },
+];
+
+fn start_transactions() -> Vec<I2cTrans> {
+    vec![
+        write(Reg::CONFIG, CFG & !0b01),
+        read(Reg::CONFIG, (CFG & !0b01) | BF::CRF),
+        read(Reg::RESULT, 0),
+        read(Reg::CONFIG, CFG & !0b01),
+        read(Reg::CONFIG, (CFG & !0b01) | BF::CRF),
+        read(Reg::RESULT, 0x789A),
+        write(Reg::CONFIG, CFG),
+        write(Reg::LOW_LIMIT, 0x5AFD),
+        write(Reg::HIGH_LIMIT, 0xBFFF),
+        write(Reg::CONFIG, CFG),
+    ]
+}
+
+#[test]
+fn can_start_in_top_zone() {
+    let sensor = new_opt3001(&start_transactions());
+    let mut sensor = sensor.into_continuous().ok().unwrap();
+    let mut tracker = ZoneTracker::new(&BOUNDARIES, FaultCount::Two);
+    assert_eq!(None, tracker.zone());
+    assert!(tracker.start(&mut sensor).is_err());
+    assert_eq!(3, block!(tracker.start(&mut sensor)).unwrap());
+    assert_eq!(Some(3), tracker.zone());
+    destroy(sensor);
+}
+
+#[test]
+fn can_cross_several_zones() {
+    let mut transactions = start_transactions();
+    transactions.extend_from_slice(&[
+        read(Reg::CONFIG, CFG | BF::FL),
+        read(Reg::RESULT, 0x3456),
+        write(Reg::LOW_LIMIT, 0x0320),
+        write(Reg::HIGH_LIMIT, 0x29C4),
+        write(Reg::CONFIG, CFG),
+        read(Reg::CONFIG, CFG),
+    ]);
+    let sensor = new_opt3001(&transactions);
+    let mut sensor = sensor.into_continuous().ok().unwrap();
+    let mut tracker = ZoneTracker::new(&BOUNDARIES, FaultCount::Two);
+    block!(tracker.start(&mut sensor)).unwrap();
+    let transition = tracker.handle_interrupt(&mut sensor).unwrap().unwrap();
+    assert_eq!(3, transition.from);
+    assert_eq!(1, transition.to);
+    assert!((transition.lux - 88.8).abs() < 0.01);
+    assert_eq!(Some(1), tracker.zone());
+    assert_eq!(
+        Events::from(Event::ConversionReady),
+        sensor.pending_events()
+    );
+    assert_eq!(None, tracker.handle_interrupt(&mut sensor).unwrap());
+    destroy(sensor);
+}
+
+#[test]
+fn cannot_start_with_unsorted_boundaries() {
+    let boundaries = [BOUNDARIES[1], BOUNDARIES[0]];
+    let sensor = new_opt3001(&[write(Reg::CONFIG, CFG & !0b01)]);
+    let mut sensor = sensor.into_continuous().ok().unwrap();
+    let mut tracker = ZoneTracker::new(&boundaries, FaultCount::Two);
+    assert_invalid_input_data!(block!(tracker.start(&mut sensor)));
+    destroy(sensor);
+}
+
+#[test]
+fn cannot_start_with_boundaries_closer_than_resolution() {
+    let boundaries = [
+        ZoneBoundary {
+            lux: 10.0,
+            hysteresis: 0.0,
+        },
+        ZoneBoundary {
+            lux: 10.004,
+            hysteresis: 0.0,
+        },
+    ];
+    let sensor = new_opt3001(&[write(Reg::CONFIG, CFG & !0b01)]);
+    let mut sensor = sensor.into_continuous().ok().unwrap();
+    let mut tracker = ZoneTracker::new(&boundaries, FaultCount::Two);
+    assert_invalid_input_data!(block!(tracker.start(&mut sensor)));
+    assert_eq!(None, tracker.zone());
+    destroy(sensor);
+}